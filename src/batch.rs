@@ -0,0 +1,122 @@
+//! Summaries of every FISPACT JSON file in a directory
+//!
+//! Intended for activation sweeps, where the same interval totals printed by
+//! the default summary are wanted for many inventories side by side.
+
+// internal
+use crate::cli::InputFormat;
+use crate::input;
+
+// neutronics toolbox
+use ntools::utils::f;
+
+// standard lib
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// external
+use anyhow::{bail, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use serde::Serialize;
+
+/// Interval totals of a single file, as a row of the combined CSV
+#[derive(Debug, Serialize)]
+pub struct SummaryRecord {
+    /// Path to the FISPACT JSON file
+    pub file: String,
+    /// Interval index within the file
+    pub interval: usize,
+    /// Irradiation time [s]
+    pub irrad_time: f64,
+    /// Cooling time [s]
+    pub cool_time: f64,
+    /// Total mass [g]
+    pub mass: f64,
+    /// Total dose rate [Sv/hr]
+    pub dose: f64,
+    /// Total activity [Bq]
+    pub activity: f64,
+}
+
+/// Interval totals for every file in `dir` matching a glob `pattern`
+///
+/// Files are read in name order, and any that fail to read are warned about
+/// and skipped rather than ending the whole run.
+pub fn summarise_directory(dir: &Path, pattern: &str) -> Result<Vec<SummaryRecord>> {
+    let files = matching_files(dir, pattern)?;
+    info!("Summarising {} files in {}", files.len(), dir.display());
+
+    let progress = ProgressBar::new(files.len() as u64);
+    if let Ok(style) = ProgressStyle::with_template("{bar:40} {pos}/{len} {msg}") {
+        progress.set_style(style);
+    }
+
+    let mut records = Vec::new();
+    for path in &files {
+        progress.set_message(f!("{}", path.display()));
+
+        match input::read_inventory(path, InputFormat::FispactII, false) {
+            Ok(inventory) => {
+                debug!(
+                    "{} intervals in {}",
+                    inventory.intervals.len(),
+                    path.display()
+                );
+                records.extend(inventory.intervals.iter().enumerate().map(|(i, interval)| {
+                    SummaryRecord {
+                        file: path.display().to_string(),
+                        interval: i,
+                        irrad_time: interval.irradiation_time,
+                        cool_time: interval.cooling_time,
+                        mass: interval.mass,
+                        dose: interval.dose.rate,
+                        activity: interval.activity,
+                    }
+                }));
+            }
+            Err(e) => progress.suspend(|| warn!("Skipping {}: {e}", path.display())),
+        }
+
+        progress.inc(1);
+    }
+
+    progress.finish_with_message("done");
+    Ok(records)
+}
+
+/// Write the combined summary of every file as CSV
+pub fn write_csv<W: Write>(records: &[SummaryRecord], writer: W) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    for record in records {
+        writer.serialize(record)?;
+    }
+
+    Ok(writer.flush()?)
+}
+
+/// Sorted list of files in `dir` matching the glob `pattern`
+fn matching_files(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display())
+    }
+
+    let full_pattern = dir.join(pattern);
+    let Some(full_pattern) = full_pattern.to_str() else {
+        bail!("Unable to use {:?} as a pattern", full_pattern.display())
+    };
+
+    let mut files = glob::glob(full_pattern)
+        .with_context(|| f!("Invalid pattern \"{pattern}\""))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .collect::<Vec<PathBuf>>();
+    files.sort();
+
+    if files.is_empty() {
+        bail!("No files matching \"{pattern}\" found in {}", dir.display())
+    }
+
+    Ok(files)
+}
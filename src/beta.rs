@@ -0,0 +1,197 @@
+//! Continuous beta spectra from endpoint energies
+//!
+//! A spectral shape file tabulates the relative probability of emission
+//! against the fraction of the endpoint energy, for example:
+//!
+//! ```text
+//! # E/E_max  weight
+//! 0.05       0.62
+//! 0.15       0.98
+//! 0.25       1.00
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored. Weights are
+//! normalised, so only their relative values matter.
+//!
+//! Without a shape file, a theoretical spectrum may instead be taken from a
+//! simplified Fermi theory of allowed beta decay.
+
+// internal
+use crate::source::Source;
+
+// neutronics toolbox
+use ntools::iaea::Record;
+use ntools::utils::f;
+
+// standard lib
+use std::fs;
+use std::path::Path;
+
+// external
+use anyhow::{bail, Context, Result};
+use log::trace;
+
+/// Read a tabulated spectral shape, normalised to unit total weight
+pub fn read_shape(path: &Path) -> Result<Vec<(f32, f32)>> {
+    let text =
+        fs::read_to_string(path).with_context(|| f!("Unable to read {:?}", path.display()))?;
+
+    let mut shape = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let point = parse_line(line)
+            .with_context(|| f!("Invalid spectral shape on line {}: \"{line}\"", n + 1))?;
+        shape.push(point);
+    }
+
+    let total = shape.iter().map(|(_, w)| w).sum::<f32>();
+    if total <= 0.0 {
+        bail!(
+            "Spectral shape in {:?} has no positive weights",
+            path.display()
+        )
+    }
+
+    Ok(shape.into_iter().map(|(x, w)| (x, w / total)).collect())
+}
+
+/// Parse a single `fraction weight` line
+fn parse_line(line: &str) -> Result<(f32, f32)> {
+    let values = line.split_whitespace().collect::<Vec<&str>>();
+    if values.len() != 2 {
+        bail!("Expected 2 values, found {}", values.len())
+    }
+
+    let fraction = values[0].parse::<f32>().context("Bad energy fraction")?;
+    let weight = values[1].parse::<f32>().context("Bad weight")?;
+
+    if !(0.0..=1.0).contains(&fraction) {
+        bail!("Energy fraction {fraction} is outside of 0-1")
+    }
+    if weight < 0.0 {
+        bail!("Negative weight {weight}")
+    }
+
+    Ok((fraction, weight))
+}
+
+/// Spread every record over the spectral shape up to its endpoint energy
+///
+/// Each record is replaced by one line per point of the shape, with the
+/// intensity of the record shared between them by weight.
+pub fn apply_shape(source: &Source, shape: &[(f32, f32)]) -> Source {
+    let mut shaped = source.clone();
+    shaped.iaea_records = source
+        .iaea_records
+        .iter()
+        .filter_map(|r| Some((r, r.energy?, r.intensity?)))
+        .flat_map(|(r, endpoint, intensity)| {
+            shape.iter().map(move |(x, w)| Record {
+                energy: Some(x * endpoint),
+                intensity: Some(w * intensity),
+                ..r.clone()
+            })
+        })
+        .filter(|r| r.energy.is_some_and(|e| e > 0.0))
+        .collect();
+    shaped
+}
+
+/// Electron rest mass energy [MeV]
+const ELECTRON_MASS_MEV: f32 = 0.51099895;
+
+/// Allowed beta spectrum from simplified Fermi theory
+///
+/// The probability at kinetic energy `T` is `p * E * (E0 - T)^2` for total
+/// energy `E` and momentum `p`, evaluated at the centre of `n_bins` uniform
+/// bins up to the endpoint. This first pass has no Coulomb correction, so
+/// the atomic number `z` does not yet change the shape. Returns the upper
+/// edge of each bin [MeV] with weights normalised to unit total.
+pub fn fermi_spectrum(endpoint_mev: f32, n_bins: usize, z: u8) -> Vec<(f32, f32)> {
+    if endpoint_mev <= 0.0 || n_bins == 0 {
+        return Vec::new();
+    }
+    trace!("Fermi spectrum to {endpoint_mev} MeV without Coulomb correction (Z = {z})");
+
+    let width = endpoint_mev / n_bins as f32;
+    let mut spectrum = (0..n_bins)
+        .map(|i| {
+            let t = (i as f32 + 0.5) * width;
+            let e = t + ELECTRON_MASS_MEV;
+            let p = (e * e - ELECTRON_MASS_MEV * ELECTRON_MASS_MEV).sqrt();
+            ((i + 1) as f32 * width, p * e * (endpoint_mev - t).powi(2))
+        })
+        .collect::<Vec<(f32, f32)>>();
+
+    let total = spectrum.iter().map(|(_, w)| w).sum::<f32>();
+    if total > 0.0 {
+        spectrum.iter_mut().for_each(|(_, w)| *w /= total);
+    }
+    spectrum
+}
+
+/// Combined Fermi spectrum of every beta branch of a nuclide
+///
+/// Branches are binned onto a common grid up to the highest endpoint energy,
+/// each weighted by its intensity [%]. Returns the upper edge of each bin
+/// [MeV] with the summed weights.
+pub fn nuclide_spectrum(source: &Source, n_bins: usize) -> Vec<(f32, f32)> {
+    let endpoints = source
+        .iaea_records
+        .iter()
+        .filter_map(|r| Some((r.energy? * 1.0e-3, r.intensity?)))
+        .collect::<Vec<(f32, f32)>>();
+
+    let e_max = endpoints.iter().map(|(e, _)| *e).fold(0.0, f32::max);
+    if e_max <= 0.0 || n_bins == 0 {
+        return Vec::new();
+    }
+
+    let z = atomic_number(&source.fispact_name).unwrap_or(0);
+    let width = e_max / n_bins as f32;
+    let mut weights = vec![0.0_f32; n_bins];
+
+    for (endpoint, intensity) in endpoints {
+        // finer binning of each branch is folded into the common grid
+        let branch_bins = ((endpoint / width).ceil() as usize).max(1);
+        for (upper, weight) in fermi_spectrum(endpoint, branch_bins, z) {
+            let centre = upper - 0.5 * endpoint / branch_bins as f32;
+            let bin = ((centre / width) as usize).min(n_bins - 1);
+            weights[bin] += weight * intensity * 1.0e-2;
+        }
+    }
+
+    weights
+        .into_iter()
+        .enumerate()
+        .map(|(i, w)| ((i + 1) as f32 * width, w))
+        .collect()
+}
+
+/// Atomic number from the element symbol of a nuclide name, e.g. Co60m
+fn atomic_number(name: &str) -> Option<u8> {
+    const ELEMENTS: [&str; 118] = [
+        "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S",
+        "Cl", "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga",
+        "Ge", "As", "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd",
+        "Ag", "Cd", "In", "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm",
+        "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os",
+        "Ir", "Pt", "Au", "Hg", "Tl", "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac", "Th", "Pa",
+        "U", "Np", "Pu", "Am", "Cm", "Bk", "Cf", "Es", "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg",
+        "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og",
+    ];
+
+    let symbol = name
+        .split(|c: char| c.is_ascii_digit())
+        .next()
+        .unwrap_or_default();
+
+    ELEMENTS
+        .iter()
+        .position(|e| e.eq_ignore_ascii_case(symbol))
+        .map(|i| (i + 1) as u8)
+}
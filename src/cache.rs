@@ -0,0 +1,74 @@
+//! Local cache of decay data fetched from the IAEA API
+//!
+//! Records are stored as one JSON file per nuclide and radiation type, e.g.
+//! `Co60_Gamma.json`, so that subsequent `--fetch` runs can work offline.
+
+// neutronics toolbox
+use ntools::iaea::{self, RecordSet};
+use ntools::utils::f;
+
+// standard lib
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+// external
+use anyhow::{Context, Result};
+use log::{trace, warn};
+
+/// Fetch records from the IAEA, using the cache directory where possible
+pub fn fetch_with_cache(
+    nuclide: &iaea::Nuclide,
+    radtype: iaea::RadType,
+    cache_dir: &Path,
+) -> Option<RecordSet> {
+    if let Some(records) = load(cache_dir, nuclide, radtype) {
+        trace!(
+            "Using cached {radtype:?} records for {}",
+            nuclide.name_with_state()
+        );
+        return Some(records);
+    }
+
+    let records = iaea::fetch_nuclide(nuclide.clone(), radtype)?;
+    if let Err(e) = store(cache_dir, nuclide, radtype, &records) {
+        warn!("{e}");
+    }
+
+    Some(records)
+}
+
+/// Check if the cache already holds records for a nuclide
+pub fn contains(cache_dir: &Path, nuclide: &iaea::Nuclide, radtype: iaea::RadType) -> bool {
+    file_path(cache_dir, nuclide, radtype).exists()
+}
+
+/// Read cached records, if any
+pub fn load(
+    cache_dir: &Path,
+    nuclide: &iaea::Nuclide,
+    radtype: iaea::RadType,
+) -> Option<RecordSet> {
+    let file = File::open(file_path(cache_dir, nuclide, radtype)).ok()?;
+    serde_json::from_reader(BufReader::new(file)).ok()
+}
+
+/// Write records to the cache, creating the directory if needed
+fn store(
+    cache_dir: &Path,
+    nuclide: &iaea::Nuclide,
+    radtype: iaea::RadType,
+    records: &RecordSet,
+) -> Result<()> {
+    fs::create_dir_all(cache_dir)
+        .with_context(|| f!("Unable to create cache {:?}", cache_dir.display()))?;
+
+    let path = file_path(cache_dir, nuclide, radtype);
+    let file = File::create(&path).with_context(|| f!("Unable to create {:?}", path.display()))?;
+    serde_json::to_writer(file, records).context("Unable to serialise records to cache")
+}
+
+/// Cache file for a nuclide and radiation type
+fn file_path(cache_dir: &Path, nuclide: &iaea::Nuclide, radtype: iaea::RadType) -> PathBuf {
+    cache_dir.join(f!("{}_{radtype:?}.json", nuclide.name_with_state()))
+}
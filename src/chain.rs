@@ -0,0 +1,74 @@
+//! Unstable daughters of the FISPACT nuclides, followed through the chain
+//!
+//! The activity of a daughter is taken as that of its parent multiplied by
+//! the branching ratio, which assumes secular equilibrium. This is only a
+//! rough estimate for daughters that FISPACT did not already report, so every
+//! added source is marked as computed from the chain.
+
+// internal
+use crate::source::{NuclideCache, Source};
+
+// neutronics toolbox
+use ntools::iaea;
+
+// standard lib
+use std::path::Path;
+
+// external
+use log::{debug, trace};
+
+/// Add `depth - 1` generations of unstable daughters to the sources
+///
+/// A depth of 1 leaves the sources untouched. Daughters already in the
+/// sources, from FISPACT or an earlier generation, are never added again.
+pub fn expand_decay_chain(
+    sources: &mut Vec<Source>,
+    depth: usize,
+    fetch: bool,
+    cache_dir: Option<&Path>,
+    database: Option<&Path>,
+    pool: &mut NuclideCache,
+) {
+    let mut parents = sources.clone();
+
+    for generation in 1..depth {
+        let mut daughters = Vec::new();
+
+        for parent in &parents {
+            for (name, branching) in parent.unstable_daughters(fetch, cache_dir, database, pool) {
+                if sources
+                    .iter()
+                    .chain(&daughters)
+                    .any(|s| s.fispact_name == name)
+                {
+                    trace!("{name} from {} is already a source", parent.fispact_name);
+                    continue;
+                }
+
+                let Ok(nuclide) = iaea::Nuclide::try_from(name.clone()) else {
+                    debug!("Could not convert daughter {name} to nuclide, skipping...");
+                    continue;
+                };
+
+                let activity = parent.fispact_activity * (branching as f64 * 1e-2);
+                let mut daughter = Source::from_manual(&name, activity, nuclide, Vec::new());
+                daughter.from_chain = true;
+                daughters.push(daughter);
+            }
+        }
+
+        debug!(
+            "Generation {} of the decay chain added {} daughters",
+            generation + 1,
+            daughters.len()
+        );
+        if daughters.is_empty() {
+            break;
+        }
+
+        sources.extend(daughters.iter().cloned());
+        parents = daughters;
+    }
+
+    sources.sort_by_key(|s| s.fispact_name.clone());
+}
@@ -0,0 +1,202 @@
+//! Self-test of the pre-fetched IAEA decay data embedded in `ntools`
+//!
+//! A handful of common activation products are loaded and compared against
+//! reference values, so that a corrupted or incomplete installation is found
+//! before any real analysis. The same nuclides may optionally be compared
+//! against the live IAEA API.
+
+// internal
+use crate::cli::{CheckIaeaArgs, Cli};
+use crate::source::Source;
+use crate::wrappers::CliRadType;
+
+// neutronics toolbox
+use ntools::iaea;
+
+// external
+use anyhow::{bail, Result};
+use log::{debug, warn};
+
+/// Tolerance on the energy of a reference line [keV]
+const ENERGY_TOLERANCE: f32 = 1.0;
+
+/// Largest acceptable intensity difference from the live API [%]
+const INTENSITY_TOLERANCE: f32 = 1.0;
+
+/// Reference nuclide with the data it is expected to have
+struct Reference {
+    /// Nuclide name, e.g. Co60
+    name: &'static str,
+    /// Radiation type of the expected data
+    rad: CliRadType,
+    /// Fewest records expected for this radiation type
+    min_records: usize,
+    /// Energy of a well known line [keV]
+    line_kev: f32,
+}
+
+/// Concise shorthand for the reference table
+const fn reference(
+    name: &'static str,
+    rad: CliRadType,
+    min_records: usize,
+    line_kev: f32,
+) -> Reference {
+    Reference {
+        name,
+        rad,
+        min_records,
+        line_kev,
+    }
+}
+
+/// Common activation products and their strongest or best known lines
+///
+/// Minimum record counts are deliberately conservative, as the exact number
+/// depends on the version of the IAEA data bundled with `ntools`.
+const REFERENCES: [Reference; 16] = [
+    reference("Na22", CliRadType::Gamma, 1, 1274.537),
+    reference("Sc46", CliRadType::Gamma, 2, 889.277),
+    reference("Cr51", CliRadType::Gamma, 1, 320.084),
+    reference("Mn54", CliRadType::Gamma, 1, 834.848),
+    reference("Co57", CliRadType::Gamma, 3, 122.061),
+    reference("Co58", CliRadType::Gamma, 1, 810.759),
+    reference("Co60", CliRadType::Gamma, 2, 1332.492),
+    reference("Zn65", CliRadType::Gamma, 1, 1115.539),
+    reference("Ag110m", CliRadType::Gamma, 10, 657.760),
+    reference("Cs134", CliRadType::Gamma, 5, 795.864),
+    reference("Eu152", CliRadType::Gamma, 20, 121.782),
+    reference("Eu154", CliRadType::Gamma, 20, 123.071),
+    reference("Ta182", CliRadType::Gamma, 10, 1121.290),
+    reference("Fe55", CliRadType::Xray, 1, 5.899),
+    reference("Co60", CliRadType::BetaMinus, 1, 317.88),
+    reference("Sr90", CliRadType::BetaMinus, 1, 546.0),
+];
+
+/// Check every reference nuclide for the chosen radiation type
+///
+/// Prints one row per nuclide and fails if any of them has missing or
+/// unexpected data.
+pub fn run(args: &CheckIaeaArgs, cli: &Cli) -> Result<()> {
+    let references = REFERENCES
+        .iter()
+        .filter(|r| r.rad == cli.rad)
+        .collect::<Vec<&Reference>>();
+
+    if references.is_empty() {
+        bail!("No reference data to check {} records against", cli.rad)
+    }
+
+    println!("\n{:-<1$}", "", 58);
+    println!(
+        " {:<10} {:>10} {:>10}   {:>12}   {}",
+        "Nuclide", "Records", "Expected", "Line [keV]", "Status"
+    );
+    println!("{:-<1$}", "", 58);
+
+    let mut n_failed = 0;
+    for reference in references {
+        let records = load(reference);
+        let status = check(reference, records.as_ref());
+        if status.is_some() {
+            n_failed += 1;
+        }
+
+        let n = records.map_or(0, |records| records.len());
+        println!(
+            " {:<10} {:>10} {:>10}   {:>12}   {}",
+            reference.name,
+            n,
+            format!(">= {}", reference.min_records),
+            reference.line_kev,
+            status.unwrap_or("ok")
+        );
+    }
+    println!();
+
+    if args.compare_fetch {
+        compare_fetched(&references);
+    }
+
+    if n_failed > 0 {
+        bail!("{n_failed} reference nuclide(s) failed, the IAEA data may be incomplete")
+    }
+
+    Ok(())
+}
+
+/// Print the differences between the built-in and live IAEA data
+///
+/// Differences are only warned about, since the live data may simply be
+/// newer than those bundled with `ntools`.
+fn compare_fetched(references: &[&Reference]) {
+    println!("{:-<1$}", "", 58);
+    println!(
+        " {:<10} {:>8} {:>8}   {:>12}   {:>12}",
+        "Nuclide", "Local", "Fetched", "dE max [keV]", "dI max [%]"
+    );
+    println!("{:-<1$}", "", 58);
+
+    for reference in references {
+        let (Some(records), Ok(nuclide)) = (
+            load(reference),
+            iaea::Nuclide::try_from(reference.name.to_string()),
+        ) else {
+            continue;
+        };
+
+        let source = Source::from_manual(reference.name, 1.0, nuclide, records);
+        let Some(comparison) = source.compare_to_fetched(reference.rad.into()) else {
+            warn!(
+                "Unable to fetch {} records for {}",
+                reference.rad, reference.name
+            );
+            continue;
+        };
+
+        println!(
+            " {:<10} {:>8} {:>8}   {:>12.3}   {:>12.3}",
+            reference.name,
+            comparison.n_local,
+            comparison.n_fetched,
+            comparison.energy_diff_max_kev,
+            comparison.intensity_diff_max_pct
+        );
+
+        if comparison.intensity_diff_max_pct > INTENSITY_TOLERANCE {
+            warn!(
+                "{} intensities differ from the IAEA API by up to {:.3}%",
+                reference.name, comparison.intensity_diff_max_pct
+            );
+        }
+    }
+    println!();
+}
+
+/// Records from the built-in data only, never fetched
+fn load(reference: &Reference) -> Option<iaea::RecordSet> {
+    let nuclide = iaea::Nuclide::try_from(reference.name.to_string()).ok()?;
+    iaea::load_nuclide(nuclide, reference.rad.into())
+}
+
+/// Reason for any failure of a reference nuclide
+fn check(reference: &Reference, records: Option<&iaea::RecordSet>) -> Option<&'static str> {
+    let Some(records) = records else {
+        return Some("no data");
+    };
+    debug!("Loaded {} records for {}", records.len(), reference.name);
+
+    if records.len() < reference.min_records {
+        return Some("too few records");
+    }
+
+    let found = records.iter().any(|r| {
+        r.energy
+            .is_some_and(|e| (e - reference.line_kev).abs() <= ENERGY_TOLERANCE)
+    });
+
+    match found {
+        true => None,
+        false => Some("line missing"),
+    }
+}
@@ -1,12 +1,14 @@
 // internal
+use crate::fileio::ExistsAction;
 use crate::wrappers::CliRadType;
 
 // command line modules
 use clap::builder::styling::{AnsiColor, Effects};
 use clap::builder::Styles;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
 // standard lib
+use std::path::PathBuf;
 use std::str::FromStr;
 
 // xternal
@@ -22,6 +24,7 @@ use anyhow::Result;
 ///
 ///  Summary of time steps:
 ///     $ fisdef file.json
+///     $ fisdef file.json.gz
 ///
 ///  Choose specific steps (e.g. 5 total steps):
 ///     $ fisdef file.json    1    => 1
@@ -61,21 +64,32 @@ use anyhow::Result;
 /// Decay data are sorted in ascending energy but this may be swapped to
 /// descending intensity with '--sort intensity'.
 ///
-#[derive(Parser)]
+#[derive(Debug, Parser)]
 #[command(
     verbatim_doc_comment,
     arg_required_else_help(true),
+    args_override_self(true),
     after_help("Note: --help shows more information and examples"),
     term_width(76),
     hide_possible_values(true),
     override_usage("fisdef <path> [options]"),
     styles=custom_style(),
+    subcommand_negates_reqs(true),
 )]
 pub struct Cli {
+    // * Subcommands
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     // * Positional
-    /// Path to fispact JSON file
+    /// Path to fispact JSON file ('-' for stdin)
+    ///
+    /// Falls back to the FISPACT_JSON_PATH environment variable if not given.
     #[arg(name = "path")]
-    pub path: String,
+    #[arg(env = "FISPACT_JSON_PATH")]
+    #[arg(hide_env_values(true))]
+    #[arg(required_unless_present("stdin"))]
+    pub path: Option<String>,
 
     /// Indices of time steps (optional)
     ///
@@ -107,7 +121,7 @@ pub struct Cli {
     ///   > Auger and conversion electron ("e")
     ///   > X-ray ("x")
     #[arg(help_heading("Data options"))]
-    #[arg(short, long, value_enum)]
+    #[arg(short, long, value_enum, global = true)]
     #[arg(hide_default_value(true))]
     #[arg(default_value_t = CliRadType::Gamma)]
     #[arg(verbatim_doc_comment)]
@@ -120,20 +134,294 @@ pub struct Cli {
     /// Alternatively, data may be sorted in descending order of relative
     /// intensity with 'i' or 'intensity'.
     #[arg(help_heading("Data options"))]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     #[arg(hide_default_value(true))]
     #[arg(default_value = "energy")]
     #[arg(value_name = "property")]
     pub sort: SortProperty,
 
+    /// Minimum nuclide activity [Bq]
+    ///
+    /// Nuclides with an activity below this value are excluded.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "Bq")]
+    pub min_activity: Option<f64>,
+
+    /// Minimum activity as a fraction of the most active nuclide
+    ///
+    /// For example, 0.01 keeps only nuclides with at least 1% of the maximum
+    /// activity. The absolute value is logged at DEBUG (-v) for use with
+    /// '--min-activity'.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "frac")]
+    pub activity_floor_fraction: Option<f64>,
+
+    /// Merge decay lines closer than this energy [keV]
+    ///
+    /// Defaults to 0 (disabled). Lines within the tolerance are combined into
+    /// one, summing intensities and taking the intensity-weighted energy.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "keV")]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value = "0.0")]
+    pub energy_dedup_tolerance: f32,
+
+    /// Keep only nuclides emitting near this energy [keV]
+    ///
+    /// For example, '--energy-query 1460' keeps nuclides with a line within
+    /// '--energy-tolerance' of 1460 keV.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "keV")]
+    pub energy_query: Option<f32>,
+
+    /// Tolerance for '--energy-query' [keV]
+    ///
+    /// Defaults to 1.0 keV.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "keV")]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value = "1.0")]
+    pub energy_tolerance: f32,
+
+    /// Interpolate sources to a time between intervals [s]
+    ///
+    /// Activities are log-linearly interpolated between the intervals either
+    /// side of the irradiation + cooling time given, and written once to
+    /// '{output}_interpolated' instead of any interval indices.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "seconds")]
+    pub interpolate_at_time: Option<f64>,
+
+    /// Print energy statistics of every nuclide
+    ///
+    /// The intensity-weighted mean, median, and mode line energies along
+    /// with the total intensity of each nuclide.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    pub stats: bool,
+
+    /// Use records from every isomeric level of each nuclide
+    ///
+    /// Ignores the isomeric state given by FISPACT, so that all available
+    /// decay data are used. Records keep their own parent energy.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    pub all_states: bool,
+
+    /// Use only the endpoint energy of beta/electron sources
+    ///
+    /// Each nuclide is reduced to a single line at its maximum endpoint
+    /// energy carrying the total intensity.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    pub endpoint_only: bool,
+
+    /// Tabulated beta spectral shape used for MCNP sources
+    ///
+    /// Two columns of fractional endpoint energy (E/E_max) and weight. Each
+    /// beta/electron line is spread over the shape in the MCNP si/sp cards.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "path")]
+    pub spectral_shape: Option<PathBuf>,
+
+    /// Column widths of the text table
+    ///
+    /// By default columns are widened to fit the longest entry, e.g. for
+    /// long isomer names, with the fixed widths as a minimum.
+    ///   > 'auto'  => fit to content
+    ///   > 'fixed' => fixed widths
+    #[arg(help_heading("Output files"))]
+    #[arg(long, value_enum, global = true)]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value_t = TextWidth::Auto)]
+    #[arg(verbatim_doc_comment)]
+    #[arg(value_name = "mode")]
+    pub text_width: TextWidth,
+
+    /// Separate text table columns with a delimiter
+    ///
+    /// For example '--text-delimiter ,' or '--text-delimiter tab'. Every row
+    /// includes the nuclide so that the table may be imported directly into a
+    /// spreadsheet.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "char")]
+    #[arg(value_parser = delimiter)]
+    pub text_delimiter: Option<char>,
+
+    /// ASCII-only text tables and MCNP files
+    ///
+    /// Symbols such as 'μ' or '±' are replaced by 'u' or '+/-', and any other
+    /// non-ASCII characters (e.g. from '--mcnp-header') by '?'.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(visible_alias = "ascii")]
+    pub text_no_unicode: bool,
+
+    /// Show intensity uncertainties in the text table
+    ///
+    /// Adds a column for the IAEA uncertainty on each intensity. JSON files
+    /// always include the propagated 'norm_uncertainty' where known.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub show_uncertainty: bool,
+
+    /// Express intensities relative to the strongest line
+    ///
+    /// The text table shows intensities relative to the strongest line of
+    /// each nuclide (= 100%), and JSON files gain a 'relative_intensity'
+    /// array. MCNP distributions always use absolute intensities.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    pub relative_intensity: bool,
+
+    /// Keep only records from specific decay modes
+    ///
+    /// Modes are compared without case to the IAEA decay mode, e.g. 'b-',
+    /// 'ec', or 'it'. Several modes may be given separated by commas. See
+    /// '--list-decay-modes' for the modes of each nuclide.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "mode")]
+    #[arg(value_delimiter = ',')]
+    pub decay_mode_filter: Option<Vec<String>>,
+
+    /// Print the decay modes available for each nuclide
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    pub list_decay_modes: bool,
+
+    /// Limit the number of records per nuclide
+    ///
+    /// Intended for quick debugging runs only. Records are truncated after
+    /// sorting, and a warning is logged for every nuclide affected.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "num")]
+    pub max_records: Option<usize>,
+
+    /// Keep only the most active nuclides
+    ///
+    /// Shorthand for keeping the first <num> nuclides with
+    /// '--sort-nuclides activity', which this replaces.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "num")]
+    pub top_by_activity: Option<usize>,
+
+    /// Skip intervals with no relevant sources without comment
+    ///
+    /// By default the reason for every empty interval is logged, i.e. no
+    /// unstable nuclides, no records for '--rad', or every record filtered
+    /// out. Skipped intervals are also left out of '--progress-json'.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    pub skip_empty_intervals: bool,
+
+    /// Follow this many generations of unstable daughters
+    ///
+    /// The default of 1 only includes nuclides reported by FISPACT. Deeper
+    /// chains add any unstable daughters not already in the inventory, with
+    /// the activity of the parent multiplied by the branching ratio. This
+    /// assumes secular equilibrium, so these sources are marked as computed
+    /// from the chain in every output.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "num")]
+    #[arg(default_value_t = 1)]
+    pub chain_depth: usize,
+
+    /// Order of nuclides in the outputs
+    ///
+    /// Defaults to alphabetical order ('name'). Alternatively:
+    ///   > 'activity'        => descending activity
+    ///   > 'photon-strength' => descending activity * particles/decay
+    ///   > 'halflife-asc'    => ascending half-life
+    ///   > 'halflife-desc'   => descending half-life
+    #[arg(help_heading("Data options"))]
+    #[arg(long, value_enum, global = true)]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value_t = NuclideSort::Name)]
+    #[arg(verbatim_doc_comment)]
+    #[arg(value_name = "property")]
+    pub sort_nuclides: NuclideSort,
+
     /// Query IAEA directly rather than pre-fetched data
     ///
     /// Note that this requires and internet connection and will be much slower
     /// than using pre-processed data.
     #[arg(help_heading("Data options"))]
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub fetch: bool,
 
+    /// Directory for caching fetched IAEA data
+    ///
+    /// Only used with '--fetch'. Decay data are read from the cache where
+    /// available, and anything fetched from the IAEA API is stored for later
+    /// offline runs. See also the 'prefetch' subcommand.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "dir")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Directory of IAEA decay data to use before the built-in data
+    ///
+    /// Files are named as '{nuclide}_{radtype}.json', e.g. 'Co60_Gamma.json',
+    /// containing a JSON list of IAEA records. This is the same layout as
+    /// '--cache-dir', so a prefetched cache works here.
+    /// Anything missing falls back to the built-in data.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "dir")]
+    pub iaea_database_path: Option<PathBuf>,
+
+    /// Read the FISPACT data from stdin
+    ///
+    /// Equivalent to giving '-' as the path, e.g. for piped workflows:
+    ///     $ fispact-runner | fisdef --stdin --mcnp
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    #[arg(verbatim_doc_comment)]
+    pub stdin: bool,
+
+    /// Format of the input file ['json', 'fispact-out', 'printlib']
+    ///
+    /// Defaults to the FISPACT-II JSON output ('json'). The tabulated text
+    /// output may be read instead with 'fispact-out', though only the
+    /// inventory blocks are used. Columnar PRINTLIB tables of nuclide
+    /// activities per cooling time are read with 'printlib'.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, value_enum, global = true)]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value_t = FileFormat::Json)]
+    #[arg(value_name = "format")]
+    pub format: FileFormat,
+
+    /// Dialect of the FISPACT JSON ['fispact-ii', 'fispact-ii-v5']
+    ///
+    /// Defaults to the current FISPACT-II JSON schema ('fispact-ii').
+    #[arg(help_heading("Data options"))]
+    #[arg(long, value_enum, global = true)]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value_t = InputFormat::FispactII)]
+    #[arg(value_name = "format")]
+    pub input_format: InputFormat,
+
+    /// Force decompression of the input file
+    ///
+    /// Files ending in '.gz' are always decompressed automatically. This flag
+    /// treats the input as gzipped regardless of extension.
+    #[arg(help_heading("Data options"))]
+    #[arg(long, global = true)]
+    pub decompress: bool,
+
     /// Prefix for output files
     ///
     /// Defaults to `step`.
@@ -141,63 +429,728 @@ pub struct Cli {
     /// Files are named `<name>_<n>.<ext>` where <n> is the index of the
     /// fispact time interval, and <ext> the appropriate extension.
     #[arg(help_heading("Output files"))]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     #[arg(value_name = "name")]
     #[arg(hide_default_value(true))]
     #[arg(default_value = "step")]
     pub output: String,
 
+    /// Zero-pad the interval index in file names
+    ///
+    /// Defaults to 0 (no padding). For example, a width of 3 gives
+    /// 'step_001.i', 'step_010.i', 'step_100.i', which keeps files in order
+    /// when sorted alphabetically for runs with many intervals.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "width")]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value = "0")]
+    pub output_prefix_pad: usize,
+
     /// Text based table
     ///
     /// Write a table of all nuclides and expected lines, excluding any that
     /// are unobserverd or have invalid data from the IAEA.
     #[arg(help_heading("Output files"))]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub text: bool,
 
+    /// Decimal places for text table branching ratios
+    ///
+    /// Defaults to 1, e.g. '(45.3%)' or '(4.5e-1%)'. Branching ratios of
+    /// 99.9% or more are left blank.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "digits")]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value = "1")]
+    pub branching_precision: usize,
+
     /// JSON output format
     ///
     /// Provides a list of every relevant nuclide with activity and
     /// energy/intensity decay data.
     #[arg(help_heading("Output files"))]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub json: bool,
 
+    /// Combined JSON including the activity-weighted spectrum
+    ///
+    /// Writes an object with the usual list of 'sources' alongside a
+    /// 'spectrum' of every unique line energy and its intensity per Bq of
+    /// total activity. Implies '--json'.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub json_combined: bool,
+
+    /// Write a separate JSON file for every nuclide
+    ///
+    /// Each source is written to '<name>_<index>_<nuclide>.json' so that it
+    /// may be read independently of the rest of the interval.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub json_per_nuclide_files: bool,
+
+    /// Compact JSON of only the activity of each nuclide
+    ///
+    /// Writes '{output}_{index}.activity.json', an object of nuclide names to
+    /// activities [Bq] in descending order, with the 'interval' index and
+    /// 'total_activity_bq' first.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub json_activity_only: bool,
+
+    /// Include half-lives and decay modes in JSON output
+    ///
+    /// Adds 'half_life_s' [s] and 'decay_mode' arrays alongside 'energy' and
+    /// 'intensity', with one entry per decay line. Omitted by default.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub json_include_half_life: bool,
+
+    /// Include branching ratios in JSON output
+    ///
+    /// Adds a 'branching_ratio' array with one entry per decay line. Omitted
+    /// by default.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub json_include_branching: bool,
+
+    /// List stable daughters of each nuclide
+    ///
+    /// Daughters with no decay data, or a half-life beyond 1e18 s, are listed
+    /// under each nuclide header of the text table and as 'stable_daughters'
+    /// in JSON output.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub show_stable_daughters: bool,
+
+    /// Number each decay line of the text table
+    ///
+    /// Prepends the zero-based position of every line within its nuclide,
+    /// e.g. '[3]', to match entries of the MCNP SI/SP cards.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub text_show_record_index: bool,
+
+    /// Order of nuclides in the text table
+    ///
+    /// Takes the same values as '--sort-nuclides', which is followed by
+    /// default. Useful when the table should be easier to read than the
+    /// order of the MCNP source.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, value_enum, global = true)]
+    #[arg(value_name = "property")]
+    pub text_sort_nuclides: Option<NuclideSort>,
+
     /// MCNP SDEF card
     ///
     /// Writes a source distribution of decay data for each nuclide, and an
     /// overall activity-based distribution to sample from.
     #[arg(help_heading("Output files"))]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub mcnp: bool,
 
     /// Starting MCNP distribution number
     ///
     /// Defaults to 100.
     #[arg(help_heading("Output files"))]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     #[arg(value_name = "num")]
     #[arg(hide_default_value(true))]
     #[arg(default_value = "100")]
     pub id: usize,
 
+    /// MCNP SDEF particle type ['auto', 'p', 'e', 'n']
+    ///
+    /// Defaults to 'auto', which infers the particle from --rad:
+    ///   > gamma, xray                     => par=p
+    ///   > electron, beta-minus, beta-plus => par=e
+    ///   > alpha                           => par=a
+    #[arg(help_heading("Output files"))]
+    #[arg(long, value_enum, global = true)]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value_t = SdefParticle::Auto)]
+    #[arg(verbatim_doc_comment)]
+    #[arg(value_name = "type")]
+    pub mcnp_sdef_par: SdefParticle,
+
+    /// Additional keywords for the MCNP SDEF card
+    ///
+    /// Appended as-is after 'par' and 'erg', e.g. "pos=0 0 0 cel=1". A
+    /// 'par=' given here overrides the particle from '--mcnp-sdef-par'.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "keywords")]
+    pub mcnp_sdef_extra: Option<String>,
+
+    /// Write a KSRC criticality source in place of the SDEF card
+    ///
+    /// WARNING: this is only an approximate starting point. There is no
+    /// geometry information, so the KSRC points are taken from
+    /// '--mcnp-kcode-pos'. The photon SI/SP distributions are still written
+    /// for any secondary photon source.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_kcode: bool,
+
+    /// Starting point of the KSRC card [cm], may be repeated
+    ///
+    /// Defaults to the origin.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(num_args = 3, action = clap::ArgAction::Append)]
+    #[arg(allow_negative_numbers = true)]
+    #[arg(value_names = ["x", "y", "z"])]
+    pub mcnp_kcode_pos: Vec<f64>,
+
+    /// Rescale activities to a total [Bq]
+    ///
+    /// Every nuclide activity is scaled by the same factor so that the total
+    /// matches the value given, e.g. to represent a larger component than the
+    /// FISPACT sample. Applies to all output formats.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "Bq")]
+    pub mcnp_normalize_to: Option<f64>,
+
+    /// Weight MCNP activities by natural isotopic abundance
+    ///
+    /// Multiplies the activity of naturally occurring radionuclides (e.g. K40,
+    /// U235, U238) by their isotopic abundance fraction. Anything else is
+    /// assumed to have an abundance of 1.0, logged at DEBUG (-v).
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_abundance_weight: bool,
+
+    /// Text to write before the MCNP source cards
+    ///
+    /// Written as-is with no wrapping. Use '@file.txt' to read multi-line text
+    /// from a file.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "text")]
+    #[arg(value_parser = text_or_file)]
+    pub mcnp_header: Option<String>,
+
+    /// Text to write after the MCNP source cards
+    ///
+    /// Written as-is with no wrapping. Use '@file.txt' to read multi-line text
+    /// from a file.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "text")]
+    #[arg(value_parser = text_or_file)]
+    pub mcnp_footer: Option<String>,
+
+    /// Run basic checks on the generated MCNP cards
+    ///
+    /// Warns about lines over 80 characters, mismatched SI/SP entries,
+    /// repeated distribution numbers, and negative energies or probabilities.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_verify: bool,
+
+    /// Print the total of every MCNP SP card to stderr
+    ///
+    /// Each nuclide total should equal its particles per decay, and the
+    /// master total the emission rate of every nuclide. Totals differing by
+    /// more than 1e-5 (relative) are also logged as a warning.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_debug_totals: bool,
+
+    /// Template for the MCNP comment on each nuclide distribution
+    ///
+    /// Placeholders are replaced by values for each nuclide: {id}, {name},
+    /// {iaea_name}, {activity}, {norm}, {n_lines}, {dominant_energy_kev},
+    /// and {halflife}. Defaults to "{name} decay data, t1/2 = {halflife},
+    /// norm = {norm} particles/decay".
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "template")]
+    pub mcnp_comment_format: Option<String>,
+
+    /// Annotate the quantities in MCNP comments with their units
+    ///
+    /// Activities are given in Bq, nuclide norms in particles/decay, and the
+    /// master distribution weights in Bq·particles/decay.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_comment_units: bool,
+
+    /// Link each MCNP nuclide distribution to the IAEA chart of nuclides
+    ///
+    /// Adds a comment with the IAEA chart of nuclides URL above its
+    /// SC card, for traceability of the decay data.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_comment_iaea_url: bool,
+
+    /// Significant figures of MCNP line energies
+    ///
+    /// Fewer significant figures shorten the SI cards, e.g. 4 writes 1173.228
+    /// keV as 1.173e+00 MeV. Defaults to 6.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "digits")]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value = "6")]
+    #[arg(value_parser = clap::value_parser!(u8).range(1..))]
+    pub mcnp_round_energies: u8,
+
+    /// MCNP photon energy cutoff [MeV]
+    ///
+    /// Photon lines below the cutoff are not transported by MCNP, so they are
+    /// removed from MCNP distributions only. Defaults to 0.001 MeV (1 keV).
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "MeV")]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value = "0.001")]
+    pub mcnp_photon_cutoff: f32,
+
+    /// Line up SI and SP card entries column by column
+    ///
+    /// Each energy and probability is padded to a common width so that the
+    /// cards are easier to review in a text editor.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub pad_si_sp: bool,
+
+    /// Write one MCNP SI/SP entry per line
+    ///
+    /// Every energy and probability goes on its own continuation line rather
+    /// than filling each line, for readable diffs under version control.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_si_sp_per_line: bool,
+
+    /// Omit the interval metadata comments from MCNP files
+    ///
+    /// By default the FISPACT file, interval times, activity, dose rate, and
+    /// fisdef version are written as comments at the top of the file.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_no_header: bool,
+
+    /// Start MCNP files with a title card
+    ///
+    /// The title names the FISPACT file, or the '--output' name if given,
+    /// with the interval and radiation type. It is followed by a comment with
+    /// the fisdef version and date, leaving only the geometry and materials
+    /// to be added.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_include_header: bool,
+
+    /// MCNP histogram distributions of bin width [MeV]
+    ///
+    /// Replaces the discrete line ('L') distribution of each nuclide with a
+    /// histogram ('H') of uniform bins. Lines closer than the bin width are
+    /// merged into a single bin.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "width")]
+    pub mcnp_histogram: Option<f32>,
+
+    /// MCNP energy distributions only, without the SDEF card
+    ///
+    /// Writes the SI/SP cards of every nuclide numbered from '--id' to
+    /// '{output}_{index}.erg.i', for use with an existing SDEF card.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_erg_card: bool,
+
+    /// Append a suggested F15 point detector tally to MCNP files
+    ///
+    /// The tally is binned uniformly over the source energy range, and the
+    /// detector position is only a placeholder to be adjusted.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_f15_tally: bool,
+
+    /// Continuous beta spectra for MCNP rather than endpoint lines
+    ///
+    /// Only valid with '--rad beta-minus' or '--rad beta-plus'. Each nuclide
+    /// is written as an 'si H' histogram of the Fermi spectrum from zero to
+    /// its endpoint energies, without any Coulomb correction.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_continuous_energy: bool,
+
+    /// Append a suggested NPS from the dose per source particle [Sv]
+    ///
+    /// The dose per source particle of the detector, e.g. from a short trial
+    /// run, gives the fraction of histories that score. An order of magnitude
+    /// NPS for a 5% error is written as a comment.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "Sv")]
+    pub mcnp_nps: Option<f64>,
+
+    /// Append a rough photon weight window block for a distance [cm]
+    ///
+    /// Commented-out WWE/WWN cards with bounds of exp(-mu*r) for water at
+    /// the dominant photon energy. Only a starting point for tuning with the
+    /// MCNP weight window generator.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "cm")]
+    pub mcnp_weight_window: Option<f32>,
+
+    /// Merge all nuclides into a single MCNP energy distribution
+    ///
+    /// Rather than one distribution per nuclide sampled by activity, every
+    /// line is weighted by its emission rate in a single SI/SP pair.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub consolidate_distributions: bool,
+
+    /// Append a surface source write card for this surface
+    ///
+    /// Writes an SSW card recording the chosen particle type as it crosses
+    /// the surface, as a starting point for a surface source run. Implies
+    /// '--consolidate-distributions'.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "surface")]
+    pub mcnp_surface_source: Option<usize>,
+
+    /// Write each MCNP nuclide distribution to a separate file
+    ///
+    /// Nuclide distributions are written to '{output}_{index}_{nuclide}.i'
+    /// and pulled into the main '{output}_{index}.i' file with 'read' cards.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub mcnp_split_files: bool,
+
+    /// Binned spectrum over all nuclides
+    ///
+    /// Aggregates every decay line into a histogram of <num> uniform bins,
+    /// weighted by activity and intensity. Written to '<name>_<n>_spectrum.txt'
+    /// and, with '--mcnp', to an 'H' distribution in '<name>_<n>_spectrum.i'.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "num")]
+    pub spectrum_bins: Option<usize>,
+
+    /// HTML summary report
+    ///
+    /// Writes a self-contained '<name>_report.html' combining the interval
+    /// summary, source statistics, decay data tables, and the most active
+    /// nuclides of every selected interval.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub report: bool,
+
+    /// CSV of activity against time for each nuclide
+    ///
+    /// Writes '<nuclide>.csv' to the output directory for every nuclide in
+    /// any selected interval, with one row per interval.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub output_per_nuclide: bool,
+
+    /// Write machine-readable progress to a JSON file
+    ///
+    /// The file is replaced after every interval with the total and completed
+    /// number of intervals, the current interval, the elapsed time [s], and
+    /// the most recent output prefix. Useful for CI runners and other
+    /// environments without a terminal.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    #[arg(value_name = "path")]
+    pub progress_json: Option<PathBuf>,
+
+    /// Gzip all output files
+    ///
+    /// Appends '.gz' to every output file name, e.g. 'step_2.i.gz'. Nuclide
+    /// files from '--mcnp-split-files' are the exception, as MCNP cannot read
+    /// gzipped files.
+    #[arg(help_heading("Output files"))]
+    #[arg(long, global = true)]
+    pub compress_output: bool,
+
+    /// What to do if an output file already exists
+    ///
+    /// Defaults to 'overwrite'. Alternatively:
+    ///   > 'skip'  => keep the existing file, e.g. to resume a batch run
+    ///   > 'error' => stop immediately, e.g. to avoid silent overwrites in CI
+    #[arg(help_heading("Output files"))]
+    #[arg(long, value_enum, global = true)]
+    #[arg(hide_default_value(true))]
+    #[arg(default_value_t = ExistsAction::Overwrite)]
+    #[arg(verbatim_doc_comment)]
+    #[arg(value_name = "action")]
+    pub output_exists_action: ExistsAction,
+
     // * Flags
     /// Verbose logging (-v, -vv)
     ///
     /// If specified, the default log level of INFO is increased to DEBUG (-v)
     /// or TRACE (-vv). Errors and Warnings are always logged unless in quiet
     /// (-q) mode.
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     #[arg(action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    /// Read default options from a TOML file
+    ///
+    /// Any options given on the command line take priority over the file.
+    #[arg(long, global = true)]
+    #[arg(value_name = "path")]
+    pub config: Option<PathBuf>,
+
+    /// Save the options used for this run to a TOML file
+    ///
+    /// Only options given explicitly are saved, and the file may be passed
+    /// straight back to '--config'. Positional arguments are not saved.
+    #[arg(long, global = true)]
+    #[arg(value_name = "path")]
+    pub write_config: Option<PathBuf>,
+
+    /// Reprocess the file every time it changes
+    ///
+    /// Outputs are regenerated whenever the FISPACT file is modified, until
+    /// interrupted with Ctrl-C. Not available when reading from stdin.
+    #[arg(long, global = true)]
+    pub watch: bool,
+
+    /// Treat inventory validation warnings as errors
+    ///
+    /// Negative times, zero mass, or negative activities in any interval will
+    /// stop execution rather than only being logged.
+    #[arg(long, global = true)]
+    pub strict: bool,
+
     /// Supress all logging
     ///
     /// Note that this overrules the --verbose flag.
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub quiet: bool,
 }
 
+impl Cli {
+    /// File name of the output prefix, without any directories
+    pub fn output_name(&self) -> &str {
+        std::path::Path::new(&self.output)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or("step")
+    }
+}
+
+/// Additional tools that do not follow the typical interval workflow
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Interpolate activities to a time between FISPACT intervals
+    ///
+    /// Activities of each nuclide are linearly interpolated between the two
+    /// intervals bounding the requested time, where the time of an interval is
+    /// taken as irradiation + cooling time. Note that this is only an
+    /// approximation of exponential decay.
+    ///
+    /// Outputs are named `<name>_interpolated.<ext>`.
+    ///
+    /// Example:
+    ///     $ fisdef interpolate file.json --time 3.6e7 --mcnp
+    #[command(verbatim_doc_comment)]
+    Interpolate(InterpolateArgs),
+
+    /// Generate outputs from manually defined decay lines
+    ///
+    /// Each line of the file is a single decay line:
+    ///     nuclide  activity_bq  energy_kev  intensity_pct
+    ///
+    /// Blank lines and lines starting with '#' are ignored. Files ending in
+    /// '.csv' are read by column name instead, requiring 'name',
+    /// 'activity_bq', 'energy_kev', and 'intensity_pct'.
+    ///
+    /// Outputs are named `<name>_manual.<ext>`.
+    ///
+    /// Example:
+    ///     $ fisdef manual lines.txt --mcnp
+    #[command(verbatim_doc_comment)]
+    Manual(ManualArgs),
+
+    /// Fetch and cache IAEA data for every nuclide in the inventory
+    ///
+    /// Unique nuclides across all intervals are fetched from the IAEA API in
+    /// parallel and stored in the '--cache-dir' directory, without writing any
+    /// output files. Subsequent runs with '--fetch --cache-dir' then work
+    /// offline.
+    ///
+    /// Example:
+    ///     $ fisdef prefetch file.json --rad gamma --cache-dir ./iaea_cache
+    #[command(verbatim_doc_comment)]
+    Prefetch(PrefetchArgs),
+
+    /// Print the JSON Schema of the '--json' output
+    ///
+    /// The schema describes the array of sources written to JSON files, and
+    /// is printed to stdout unless '--json-schema-output' is given.
+    ///
+    /// Example:
+    ///     $ fisdef schema --json-schema-output fisdef.schema.json
+    #[command(verbatim_doc_comment)]
+    Schema(SchemaArgs),
+
+    /// Show which radiation types have decay data for a nuclide
+    ///
+    /// Every '--rad' type is listed with the number of records and the
+    /// energy of the most intense line, including those without any data.
+    ///
+    /// Example:
+    ///     $ fisdef list-radtypes Co60
+    #[command(verbatim_doc_comment)]
+    ListRadtypes(ListRadtypesArgs),
+    /// Combine the sources of several MCNP files into one
+    ///
+    /// Reads the master distribution and nuclide distributions of each file
+    /// written by fisdef, renumbers them from '--id', and samples each file
+    /// by its total strength in a new master distribution.
+    ///
+    /// Outputs are named `step_merged.i`, or as given by '--output'.
+    ///
+    /// Example:
+    ///     $ fisdef merge-mcnp zone1_2.i zone2_2.i --id 200
+    #[command(verbatim_doc_comment)]
+    MergeMcnp(MergeMcnpArgs),
+
+    /// Plot the source spectrum of an interval
+    ///
+    /// Draws a bar chart of the emission rate of every line against its
+    /// energy, coloured by nuclide, with the most active named in a legend.
+    /// Requires fisdef to be built with the 'plot' feature.
+    ///
+    /// Outputs are named `<name>_<interval>.png`.
+    ///
+    /// Example:
+    ///     $ fisdef plot file.json --interval 2 --rad gamma
+    #[command(verbatim_doc_comment)]
+    Plot(PlotArgs),
+
+    /// Check the built-in IAEA data against reference nuclides
+    ///
+    /// Common activation products are loaded from the data bundled with
+    /// ntools, and each must have at least the expected number of '--rad'
+    /// records, including a well known line. Nothing is fetched unless
+    /// '--compare-fetch' is given, which also lists the largest differences
+    /// from the live IAEA API.
+    ///
+    /// Example:
+    ///     $ fisdef check-iaea --rad gamma --compare-fetch
+    #[command(verbatim_doc_comment)]
+    CheckIaea(CheckIaeaArgs),
+
+    /// Summarise every FISPACT JSON file in a directory
+    ///
+    /// Files matching '--pattern' are read in name order, and the totals of
+    /// every interval are written to a single CSV with the columns:
+    ///     file, interval, irrad_time, cool_time, mass, dose, activity
+    ///
+    /// Units are [s], [g], [Sv/hr] and [Bq]. Files that fail to read are
+    /// skipped with a warning.
+    ///
+    /// Outputs are named `step_summary.csv`, or as given by '--output'.
+    ///
+    /// Example:
+    ///     $ fisdef summarise-all ./sweep --pattern "run_*.json"
+    #[command(verbatim_doc_comment)]
+    SummariseAll(SummariseAllArgs),
+}
+
+/// Arguments for the `summarise-all` subcommand
+#[derive(Debug, Args)]
+pub struct SummariseAllArgs {
+    /// Directory of fispact JSON files
+    #[arg(name = "dir")]
+    pub dir: PathBuf,
+
+    /// Glob pattern of file names within the directory
+    #[arg(long)]
+    #[arg(default_value = "*.json")]
+    #[arg(value_name = "glob")]
+    pub pattern: String,
+}
+
+/// Arguments for the `check-iaea` subcommand
+#[derive(Debug, Args)]
+pub struct CheckIaeaArgs {
+    /// Also compare the built-in data against the live IAEA API
+    #[arg(long)]
+    pub compare_fetch: bool,
+}
+
+/// Arguments for the `plot` subcommand
+#[derive(Debug, Args)]
+pub struct PlotArgs {
+    /// Path to fispact JSON file
+    #[arg(name = "path")]
+    pub path: String,
+
+    /// Interval to plot, defaults to the final interval
+    #[arg(long)]
+    #[arg(value_name = "index")]
+    pub interval: Option<usize>,
+}
+
+/// Arguments for the `merge-mcnp` subcommand
+#[derive(Debug, Args)]
+pub struct MergeMcnpArgs {
+    /// Paths to MCNP files written by fisdef
+    #[arg(name = "paths")]
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+}
+
+/// Arguments for the `list-radtypes` subcommand
+#[derive(Debug, Args)]
+pub struct ListRadtypesArgs {
+    /// Nuclide name, e.g. Co60
+    #[arg(name = "nuclide")]
+    pub nuclide: String,
+}
+
+/// Arguments for the `schema` subcommand
+#[derive(Debug, Args)]
+pub struct SchemaArgs {
+    /// Write the schema to a file instead of stdout
+    #[arg(long)]
+    #[arg(value_name = "path")]
+    pub json_schema_output: Option<PathBuf>,
+}
+
+/// Arguments for the `prefetch` subcommand
+#[derive(Debug, Args)]
+pub struct PrefetchArgs {
+    /// Path to fispact JSON file
+    #[arg(name = "path")]
+    pub path: String,
+}
+
+/// Arguments for the `manual` subcommand
+#[derive(Debug, Args)]
+pub struct ManualArgs {
+    /// Path to the source definition file
+    #[arg(name = "path")]
+    pub path: String,
+}
+
+/// Arguments for the `interpolate` subcommand
+#[derive(Debug, Args)]
+pub struct InterpolateArgs {
+    /// Path to fispact JSON file
+    #[arg(name = "path")]
+    pub path: String,
+
+    /// Time of interest [s]
+    #[arg(long)]
+    #[arg(value_name = "seconds")]
+    pub time: f64,
+}
+
 /// Customise the colour styles for clap v4
 fn custom_style() -> Styles {
     Styles::styled()
@@ -221,6 +1174,28 @@ pub fn init_logging(cli: &Cli) -> Result<()> {
         .init()?)
 }
 
+/// Take text literally, or read it from a file if prefixed with '@'
+fn text_or_file(s: &str) -> Result<String, String> {
+    match s.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}")),
+        None => Ok(s.to_string()),
+    }
+}
+
+/// Single character delimiter, allowing 'tab' or '\t' for tabs
+fn delimiter(s: &str) -> Result<char, String> {
+    match s {
+        "tab" | "\\t" | "\t" => Ok('\t'),
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(format!("expected a single character, found \"{s}\"")),
+            }
+        }
+    }
+}
+
 /// User input that can handle multiple ways of defining interval index
 ///
 /// e.g. single number : 1
@@ -284,6 +1259,54 @@ impl FromStr for MultiRange {
     }
 }
 
+/// Supported FISPACT output formats
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum FileFormat {
+    #[default]
+    Json,
+    FispactOut,
+    Printlib,
+}
+
+/// Supported FISPACT JSON schema variants
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum InputFormat {
+    #[default]
+    #[value(name = "fispact-ii")]
+    FispactII,
+    #[value(name = "fispact-ii-v5")]
+    FispactIIV5,
+}
+
+/// User input for ordering nuclides
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum NuclideSort {
+    #[default]
+    Name,
+    Activity,
+    PhotonStrength,
+    HalflifeAsc,
+    HalflifeDesc,
+}
+
+/// User input for text table column widths
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum TextWidth {
+    #[default]
+    Auto,
+    Fixed,
+}
+
+/// Particle designators for the MCNP SDEF card
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum SdefParticle {
+    #[default]
+    Auto,
+    P,
+    E,
+    N,
+}
+
 /// User input for various sorting methods
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
 pub enum SortProperty {
@@ -0,0 +1,279 @@
+//! Saving and loading command line options as TOML
+//!
+//! Options are stored by their long name, e.g. `rad = "gamma"` or
+//! `mcnp = true`, so that a saved file reads like the original command.
+//! Positional arguments (path and indices) are never saved.
+
+// internal
+use crate::cli::Cli;
+use crate::fileio::{self, ExistsAction};
+
+// standard lib
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+
+// external
+use anyhow::{bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, CommandFactory};
+
+/// Options that only make sense for the run that used them
+const SKIPPED: [&str; 2] = ["config", "write-config"];
+
+/// Serialise every option given on the command line to TOML
+///
+/// Flags that are not set are omitted for compactness.
+pub fn cli_to_toml(matches: &ArgMatches) -> Result<String> {
+    let mut table = toml::Table::new();
+
+    for arg in Cli::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long().filter(|l| !SKIPPED.contains(l)) else {
+            continue;
+        };
+
+        if matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+
+        let value = match arg.get_action() {
+            ArgAction::SetTrue => toml::Value::Boolean(matches.get_flag(id)),
+            ArgAction::Count => toml::Value::Integer(matches.get_count(id) as i64),
+            _ => {
+                let Some(raw) = matches.get_raw(id) else {
+                    continue;
+                };
+                let mut values = raw
+                    .map(|v| toml::Value::String(v.to_string_lossy().to_string()))
+                    .collect::<Vec<toml::Value>>();
+
+                match values.len() {
+                    1 => values.remove(0),
+                    _ => toml::Value::Array(values),
+                }
+            }
+        };
+
+        if value != toml::Value::Boolean(false) {
+            table.insert(long.to_string(), value);
+        }
+    }
+
+    toml::to_string(&table).context("Unable to serialise options to TOML")
+}
+
+/// Write the options given on the command line to a TOML file
+pub fn write(matches: &ArgMatches, path: &Path, exists: ExistsAction) -> Result<()> {
+    let Some(mut f) = fileio::open_named(path, exists)? else {
+        return Ok(());
+    };
+
+    f.write_all(cli_to_toml(matches)?.as_bytes())
+        .with_context(|| format!("Unable to write config to {}", path.display()))?;
+    f.finish()
+}
+
+/// Insert options from any `--config` file ahead of the command line options
+///
+/// Any option also given on the command line is dropped from the config
+/// entirely, so that repeated and counted options are replaced rather than
+/// added to.
+pub fn with_config_args(args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let Some(path) = config_path(&args) else {
+        return Ok(args);
+    };
+
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read config from {path}"))?;
+    let table = text
+        .parse::<toml::Table>()
+        .with_context(|| format!("Invalid TOML in {path}"))?;
+
+    let mut command = Cli::command();
+    command.build();
+
+    // a command line that does not parse on its own is reported later anyway
+    let given = command.clone().try_get_matches_from(args.clone()).ok();
+
+    let mut config_args = Vec::new();
+    for (long, value) in table {
+        let Some(arg) = command
+            .get_arguments()
+            .find(|a| a.get_long() == Some(long.as_str()))
+        else {
+            bail!("Unknown option \"{long}\" in {path}")
+        };
+
+        let id = arg.get_id().as_str();
+        if given
+            .as_ref()
+            .is_some_and(|m| m.value_source(id) == Some(ValueSource::CommandLine))
+        {
+            continue;
+        }
+
+        config_args.extend(option_args(arg, &long, value, &path)?);
+    }
+
+    // everything else is left exactly as given after the program name
+    let mut args = args.into_iter();
+    Ok(args
+        .next()
+        .into_iter()
+        .chain(config_args)
+        .chain(args)
+        .collect())
+}
+
+/// Arguments for a single option of the config file
+///
+/// Appended options are given once per value, or once per group of values
+/// for those taking a fixed number each, e.g. three coordinates.
+fn option_args(arg: &Arg, long: &str, value: toml::Value, path: &str) -> Result<Vec<OsString>> {
+    let flag = OsString::from(format!("--{long}"));
+
+    let args = match value {
+        toml::Value::Boolean(true) => vec![flag],
+        toml::Value::Boolean(false) => Vec::new(),
+        toml::Value::Integer(n) if matches!(arg.get_action(), ArgAction::Count) => {
+            vec![flag; n.max(0) as usize]
+        }
+        toml::Value::Array(values) => {
+            let values = values
+                .iter()
+                .map(|v| match v.as_str() {
+                    Some(s) => Ok(OsString::from(s)),
+                    None => bail!("Expected only strings in \"{long}\" of {path}"),
+                })
+                .collect::<Result<Vec<OsString>>>()?;
+
+            let chunk = match arg.get_action() {
+                ArgAction::Append => arg
+                    .get_num_args()
+                    .filter(|n| n.min_values() == n.max_values())
+                    .map_or(1, |n| n.min_values().max(1)),
+                _ => values.len().max(1),
+            };
+
+            values
+                .chunks(chunk)
+                .flat_map(|c| std::iter::once(flag.clone()).chain(c.iter().cloned()))
+                .collect()
+        }
+        toml::Value::String(s) => vec![flag, s.into()],
+        other => vec![flag, other.to_string().into()],
+    };
+
+    Ok(args)
+}
+
+/// Find the value of `--config` in the raw arguments, if any
+fn config_path(args: &[OsString]) -> Option<String> {
+    let mut args = args.iter().map(|a| a.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(|p| p.to_string());
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::FromArgMatches;
+
+    fn matches(args: &[&str]) -> ArgMatches {
+        Cli::command()
+            .try_get_matches_from(
+                with_config_args(args.iter().map(OsString::from).collect()).unwrap(),
+            )
+            .unwrap()
+    }
+
+    fn temp_config(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("fisdef_{}_{name}.toml", std::process::id()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_config("round_trip");
+        let first = matches(&[
+            "fisdef",
+            "file.json",
+            "--rad",
+            "beta-minus",
+            "--mcnp",
+            "--text",
+            "-vv",
+            "--decay-mode-filter",
+            "b-",
+            "--decay-mode-filter",
+            "ec",
+            "--mcnp-kcode-pos",
+            "1",
+            "2",
+            "3",
+            "--mcnp-kcode-pos",
+            "-4",
+            "5",
+            "6",
+        ]);
+        write(&first, &path, ExistsAction::Overwrite).unwrap();
+
+        let config = path.to_string_lossy().to_string();
+        let second = matches(&["fisdef", "file.json", "--config", &config]);
+        std::fs::remove_file(&path).unwrap();
+
+        let expected = Cli::from_arg_matches(&first).unwrap();
+        let mut found = Cli::from_arg_matches(&second).unwrap();
+        found.config = None;
+        assert_eq!(format!("{expected:?}"), format!("{found:?}"));
+    }
+
+    #[test]
+    fn command_line_takes_priority() {
+        let path = temp_config("priority");
+        std::fs::write(&path, "rad = \"beta-minus\"\nmcnp = true\n").unwrap();
+
+        let config = path.to_string_lossy().to_string();
+        let matches = matches(&["fisdef", "file.json", "--config", &config, "--rad", "gamma"]);
+        std::fs::remove_file(&path).unwrap();
+
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        assert_eq!(cli.rad, crate::wrappers::CliRadType::Gamma);
+        assert!(cli.mcnp);
+    }
+
+    #[test]
+    fn command_line_replaces_repeated_options() {
+        let path = temp_config("repeated");
+        std::fs::write(
+            &path,
+            "verbose = 2\nmcnp-kcode-pos = [\"1\", \"2\", \"3\", \"4\", \"5\", \"6\"]\n",
+        )
+        .unwrap();
+
+        let config = path.to_string_lossy().to_string();
+        let matches = matches(&[
+            "fisdef",
+            "file.json",
+            "--config",
+            &config,
+            "-v",
+            "--mcnp-kcode-pos",
+            "0",
+            "0",
+            "1",
+        ]);
+        std::fs::remove_file(&path).unwrap();
+
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        assert_eq!(cli.verbose, 1);
+        assert_eq!(cli.mcnp_kcode_pos, vec![0.0, 0.0, 1.0]);
+    }
+}
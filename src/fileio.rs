@@ -0,0 +1,194 @@
+//! Opening output files for the various writers
+//!
+//! Every output format writes to something implementing `Write`, so that tests
+//! or library users may pass an in-memory buffer instead of a file.
+
+// neutronics toolbox
+use ntools::utils::f;
+
+// standard lib
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+// external
+use anyhow::{bail, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+
+/// What to do when an output file already exists
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum ExistsAction {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Leave the existing file untouched
+    Skip,
+    /// Stop with an error
+    Error,
+}
+
+/// Output file, gzipped if requested
+///
+/// Dropping a gzip stream silently loses any error writing its trailer, so
+/// every output must be closed with `finish()` once written.
+pub enum Output {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+}
+
+impl Output {
+    /// Flush everything to the file, including the gzip trailer
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Output::Plain(mut f) => f.flush(),
+            Output::Gzip(encoder) => encoder.finish().and_then(|mut f| f.flush()),
+        }
+        .context("Unable to finish writing output file")
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Plain(f) => f.write(buf),
+            Output::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Plain(f) => f.flush(),
+            Output::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Try to create a file, including all dirs, with a default to fallback on
+///
+/// If `compress` is set the file is gzipped and `.gz` appended to the name.
+/// Existing files are handled by `exists`, and `None` is returned for any that
+/// are skipped so that nothing is written.
+pub fn open_output(
+    path: &Path,
+    extension: &str,
+    default: &str,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<Option<Output>> {
+    let mut p = path.to_path_buf();
+
+    let (extension, default) = match compress {
+        true => (f!("{extension}.gz"), f!("{default}.gz")),
+        false => (extension.to_string(), default.to_string()),
+    };
+
+    // Ensure all parent directories exist
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            warn!("{e}. Falling back to working directory.");
+            p = p.file_name().expect("No file name provided").into();
+        }
+    }
+
+    let target = p.with_extension(&extension);
+    if target.exists() {
+        match exists {
+            ExistsAction::Overwrite => (),
+            ExistsAction::Skip => {
+                info!("Skipping existing {}", target.display());
+                return Ok(None);
+            }
+            ExistsAction::Error => bail!("Output file {} already exists", target.display()),
+        }
+    }
+
+    // Create the file, fall back to a default if not
+    info!("Writing {}", target.display());
+    let f = File::create(target).or_else(|e| {
+        warn!("{e}. Falling back to \"{default}\".",);
+        File::create(&default).context("Unable to create fallback file")
+    })?;
+
+    Ok(Some(match compress {
+        true => Output::Gzip(GzEncoder::new(f, Compression::default())),
+        false => Output::Plain(f),
+    }))
+}
+
+/// Open a file named exactly as given, without compression
+///
+/// For paths chosen in full by the user, such as a config or schema file, so
+/// the file name itself is the fallback.
+pub fn open_named(path: &Path, exists: ExistsAction) -> Result<Option<Output>> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let default = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("fisdef.out");
+    open_output(path, extension, default, false, exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Existing file in a fresh directory, returning the path without extension
+    fn existing(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("fisdef_{}_{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("step_0");
+        fs::write(path.with_extension("txt"), "original").unwrap();
+        path
+    }
+
+    #[test]
+    fn overwrite_existing() {
+        let path = existing("overwrite");
+        let mut f = open_output(&path, "txt", "step_0.txt", false, ExistsAction::Overwrite)
+            .unwrap()
+            .unwrap();
+        f.write_all(b"replaced").unwrap();
+        f.finish().unwrap();
+
+        let text = fs::read_to_string(path.with_extension("txt")).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(text, "replaced");
+    }
+
+    #[test]
+    fn gzip_is_finished() {
+        let path = existing("gzip");
+        let mut f = open_output(&path, "txt", "step_0.txt", true, ExistsAction::Overwrite)
+            .unwrap()
+            .unwrap();
+        f.write_all(b"compressed").unwrap();
+        f.finish().unwrap();
+
+        let file = File::open(path.with_extension("txt.gz")).unwrap();
+        let text = std::io::read_to_string(flate2::read::GzDecoder::new(file)).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(text, "compressed");
+    }
+
+    #[test]
+    fn skip_existing() {
+        let path = existing("skip");
+        let f = open_output(&path, "txt", "step_0.txt", false, ExistsAction::Skip).unwrap();
+
+        let text = fs::read_to_string(path.with_extension("txt")).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert!(f.is_none());
+        assert_eq!(text, "original");
+    }
+
+    #[test]
+    fn error_on_existing() {
+        let path = existing("error");
+        let f = open_output(&path, "txt", "step_0.txt", false, ExistsAction::Error);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert!(f.is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Reader for the tabulated FISPACT-II text output (`.out`)
+//!
+//! Each `TIME INTERVAL` starts a new interval. From the nuclide table headed
+//! by `NUCLIDE  ATOMS  GRAMS  Bq ...` only the atoms, grams, activity, and
+//! half-life (last column) of each row are kept. The interval totals are read
+//! from the `COOLING TIME IS`, `ELAPSED TIME IS`, `TOTAL ACTIVITY`,
+//! `TOTAL MASS` (kg), and `DOSE RATE` lines.
+//!
+//! Heating, ingestion and inhalation doses, flux, and the gamma spectra are
+//! not read and are left as zero or empty, as are the run name and timestamp.
+
+// neutronics toolbox
+use ntools::fispact::Inventory;
+use ntools::utils::f;
+
+// standard lib
+use std::fs;
+use std::path::Path;
+
+// external
+use anyhow::{bail, Context, Result};
+use log::{debug, trace};
+use serde_json::{json, Value};
+
+/// Read the inventory data from a FISPACT-II `.out` file
+pub fn read(path: &Path) -> Result<Inventory> {
+    let text =
+        fs::read_to_string(path).with_context(|| f!("Unable to read {:?}", path.display()))?;
+    from_str(&text)
+}
+
+/// Read the inventory data from the text of a FISPACT-II `.out` file
+pub fn from_str(text: &str) -> Result<Inventory> {
+    let intervals = parse_intervals(text);
+    if intervals.is_empty() {
+        bail!("No inventory data found")
+    }
+    debug!("Found {} inventory intervals", intervals.len());
+    to_inventory(&intervals)
+}
+
+/// Assemble raw intervals into the structure of the FISPACT-II JSON output
+pub(crate) fn to_inventory(intervals: &[RawInterval]) -> Result<Inventory> {
+    let data = json!({
+        "run_data": {
+            "timestamp": "",
+            "run_name": "",
+            "flux_name": "",
+        },
+        "inventory_data": intervals.iter().map(RawInterval::to_json).collect::<Vec<Value>>(),
+    });
+
+    serde_json::from_value(data).context("Unable to convert FISPACT output to an inventory")
+}
+
+/// Minimal set of data collected for each interval
+#[derive(Debug, Default)]
+pub(crate) struct RawInterval {
+    pub(crate) irradiation_time: f64,
+    pub(crate) cooling_time: f64,
+    pub(crate) mass: f64,
+    pub(crate) activity: f64,
+    pub(crate) dose: f64,
+    pub(crate) nuclides: Vec<RawNuclide>,
+}
+
+impl RawInterval {
+    fn to_json(&self) -> Value {
+        json!({
+            "irradiation_time": self.irradiation_time,
+            "cooling_time": self.cooling_time,
+            "flux": 0.0,
+            "alpha_heat": 0.0,
+            "beta_heat": 0.0,
+            "gamma_heat": 0.0,
+            "ingestion_dose": 0.0,
+            "inhalation_dose": 0.0,
+            "total_mass": self.mass,
+            "total_activity": self.activity,
+            "dose_rate": {
+                "type": "",
+                "distance": 0.0,
+                "mass": 0.0,
+                "dose": self.dose,
+            },
+            "nuclides": self.nuclides.iter().map(RawNuclide::to_json).collect::<Vec<Value>>(),
+            "gamma_spectrum": {
+                "boundaries": [],
+                "values": [],
+            },
+        })
+    }
+}
+
+/// Single row of the nuclide table
+#[derive(Debug, Clone)]
+pub(crate) struct RawNuclide {
+    pub(crate) element: String,
+    pub(crate) isotope: u32,
+    pub(crate) state: String,
+    pub(crate) atoms: f64,
+    pub(crate) grams: f64,
+    pub(crate) activity: f64,
+    pub(crate) half_life: f64,
+}
+
+impl RawNuclide {
+    fn to_json(&self) -> Value {
+        json!({
+            "element": self.element,
+            "isotope": self.isotope,
+            "state": self.state,
+            "half_life": self.half_life,
+            "grams": self.grams,
+            "activity": self.activity,
+            "alpha_heat": 0.0,
+            "beta_heat": 0.0,
+            "gamma_heat": 0.0,
+            "heat": 0.0,
+            "dose": 0.0,
+            "ingestion": 0.0,
+            "inhalation": 0.0,
+            "atoms": self.atoms,
+        })
+    }
+}
+
+/// Walk the file line by line and collect every inventory block
+fn parse_intervals(text: &str) -> Vec<RawInterval> {
+    let mut intervals: Vec<RawInterval> = Vec::new();
+    let mut in_table = false;
+    let mut irradiation_time = 0.0;
+
+    for line in text.lines() {
+        let upper = line.to_uppercase();
+
+        if upper.contains("TIME INTERVAL") {
+            trace!("New interval: {}", line.trim());
+            intervals.push(RawInterval {
+                irradiation_time,
+                ..Default::default()
+            });
+            in_table = false;
+            continue;
+        }
+
+        let Some(interval) = intervals.last_mut() else {
+            continue;
+        };
+
+        if upper.contains("NUCLIDE") && upper.contains("ATOMS") {
+            in_table = true;
+            continue;
+        }
+
+        if in_table {
+            if let Some(nuclide) = parse_nuclide(line) {
+                interval.nuclides.push(nuclide);
+                continue;
+            }
+
+            // the table is over at the first line that is not a nuclide
+            if upper.contains("TOTAL") {
+                in_table = false;
+            }
+        }
+
+        if let Some(value) = value_after(&upper, "COOLING TIME IS") {
+            interval.cooling_time = value;
+        } else if let Some(value) = value_after(&upper, "ELAPSED TIME IS") {
+            irradiation_time = value;
+            interval.irradiation_time = value;
+        } else if let Some(value) = value_after(&upper, "TOTAL ACTIVITY") {
+            interval.activity = value;
+        } else if let Some(value) = value_after(&upper, "TOTAL MASS") {
+            // reported in kg, but grams are expected
+            interval.mass = value * 1.0e3;
+        } else if let Some(value) = value_after(&upper, "DOSE RATE") {
+            interval.dose = value;
+        }
+    }
+
+    intervals
+}
+
+/// Parse a nuclide table row, e.g. `  12  Co 60m  1.2E+10  2.3E-03  4.5E+07 ...`
+fn parse_nuclide(line: &str) -> Option<RawNuclide> {
+    let mut tokens = line.split_whitespace().peekable();
+
+    // optional leading row number
+    if tokens.peek()?.parse::<usize>().is_ok() {
+        tokens.next();
+    }
+
+    let element = tokens.next()?;
+    if !element.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    // mass number with an optional isomer state suffix
+    let isotope = tokens.next()?;
+    let split = isotope
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(isotope.len());
+    let (mass, state) = isotope.split_at(split);
+
+    let values = tokens
+        .filter_map(|t| t.parse::<f64>().ok())
+        .collect::<Vec<f64>>();
+
+    // atoms, grams, activity, ..., half-life as a minimum
+    if values.len() < 4 {
+        return None;
+    }
+
+    Some(RawNuclide {
+        element: element.to_string(),
+        isotope: mass.parse().ok()?,
+        state: state.to_string(),
+        atoms: values[0],
+        grams: values[1],
+        activity: values[2],
+        half_life: *values.last()?,
+    })
+}
+
+/// Find the first number following a label on a line
+fn value_after(line: &str, label: &str) -> Option<f64> {
+    let start = line.find(label)? + label.len();
+    line[start..]
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .find_map(|t| t.parse::<f64>().ok())
+}
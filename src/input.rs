@@ -0,0 +1,63 @@
+//! Readers for the supported FISPACT JSON dialects
+//!
+//! New schema variants only need a new `InputFormat` and a branch here, rather
+//! than changes to the `ntools` dependency.
+
+// internal
+use crate::cli::InputFormat;
+
+// neutronics toolbox
+use ntools::fispact::{self, Inventory};
+use ntools::utils::f;
+
+// standard lib
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+// external
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use log::debug;
+
+/// Read a FISPACT JSON inventory in the given dialect
+pub fn read_inventory(path: &Path, format: InputFormat, decompress: bool) -> Result<Inventory> {
+    match format {
+        InputFormat::FispactII => read_json(path, decompress),
+        InputFormat::FispactIIV5 => {
+            // no schema differences are known yet, so this is a placeholder
+            debug!("Reading as FISPACT-II v5 JSON");
+            read_json(path, decompress)
+        }
+    }
+}
+
+/// Read a FISPACT JSON inventory from any reader, e.g. stdin
+pub fn read_inventory_reader<R: Read>(
+    reader: R,
+    format: InputFormat,
+    decompress: bool,
+) -> Result<Inventory> {
+    debug!("Reading as {format:?} JSON");
+
+    let reader = BufReader::new(reader);
+    match decompress {
+        true => serde_json::from_reader(GzDecoder::new(reader)),
+        false => serde_json::from_reader(reader),
+    }
+    .context("Unable to parse FISPACT JSON")
+}
+
+/// Read the FISPACT inventory, decompressing gzipped files if needed
+fn read_json(path: &Path, decompress: bool) -> Result<Inventory> {
+    let is_gzip = path.extension().is_some_and(|ext| ext == "gz");
+
+    if !(decompress || is_gzip) {
+        return Ok(fispact::read_json(path)?);
+    }
+
+    debug!("Decompressing {:?}", path.display());
+    let file = File::open(path).with_context(|| f!("Unable to open {:?}", path.display()))?;
+    let reader = BufReader::new(GzDecoder::new(file));
+    serde_json::from_reader(reader).context("Unable to parse decompressed FISPACT JSON")
+}
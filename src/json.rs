@@ -1,16 +1,292 @@
 // internal
-use crate::create_file_with_fallback;
+use crate::cli::Cli;
+use crate::fileio::{open_output, ExistsAction};
 use crate::source::Source;
+use crate::spectrum::aggregate_spectrum;
+
+// neutronics toolbox
+use ntools::utils::OptionExt;
 
 // standard lib
+use std::io::Write;
 use std::path::Path;
 
 // external
 use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Source data with its share of the total activity
+#[derive(Serialize)]
+struct JsonSource<'a> {
+    #[serde(flatten)]
+    source: &'a Source,
+    activity_fraction: f64,
+    cumulative_activity_fraction: f64,
+    photon_yield_per_second: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    norm_uncertainty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_intensity: Option<Vec<Option<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    half_life_s: Option<Vec<Option<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decay_mode: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branching_ratio: Option<Vec<Option<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stable_daughters: Option<Vec<String>>,
+}
+
+/// Layout of a serialised `JsonSource`, only used to generate the schema
+///
+/// `Source` is serialised by hand, so this must be kept in step with both.
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(rename = "Source")]
+struct SourceSchema {
+    /// Nuclide name as written by FISPACT-II
+    name_fispact: String,
+    /// Nuclide name as used by the IAEA chart of nuclides
+    name_iaea: String,
+    /// Activity of the nuclide [Bq]
+    activity: f64,
+    /// Energy of each decay line [keV]
+    energy: Vec<Option<f32>>,
+    /// Intensity of each decay line [%]
+    intensity: Vec<Option<f32>>,
+    /// Activity estimated from the parent with '--chain-depth', not FISPACT
+    from_chain: bool,
+    /// Fraction of the total activity
+    activity_fraction: f64,
+    /// Cumulative fraction of the total activity in descending order
+    cumulative_activity_fraction: f64,
+    /// Particles emitted per second, i.e. activity * particles/decay
+    photon_yield_per_second: f64,
+    /// Uncertainty on the particles per decay, where known
+    norm_uncertainty: Option<f64>,
+    /// Intensity relative to the strongest line [%], with '--relative-intensity'
+    relative_intensity: Option<Vec<Option<f32>>>,
+    /// Half-life of the parent of each decay line [s], with '--json-include-half-life'
+    half_life_s: Option<Vec<Option<f32>>>,
+    /// Decay mode of each decay line, with '--json-include-half-life'
+    decay_mode: Option<Vec<String>>,
+    /// Branching ratio of each decay line, with '--json-include-branching'
+    branching_ratio: Option<Vec<Option<f32>>>,
+    /// Stable daughters of any decay line, with '--show-stable-daughters'
+    stable_daughters: Option<Vec<String>>,
+}
+
+/// JSON Schema for the array of sources written by `write`
+pub fn schema() -> Result<String> {
+    let schema = schemars::schema_for!(Vec<SourceSchema>);
+    serde_json::to_string_pretty(&schema).context("Unable to serialise JSON schema")
+}
+
+/// Options for the JSON output
+#[derive(Debug, Clone)]
+pub struct JsonConfig {
+    /// Include intensities relative to the strongest line
+    pub relative_intensity: bool,
+    /// Write an object with both the sources and the aggregate spectrum
+    pub combined: bool,
+    /// Include the half-life and decay mode of every line
+    pub half_life: bool,
+    /// Include the branching ratio of every line
+    pub branching: bool,
+    /// Include the stable daughters of each nuclide
+    pub stable_daughters: bool,
+}
+
+impl From<&Cli> for JsonConfig {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            relative_intensity: cli.relative_intensity,
+            combined: cli.json_combined,
+            half_life: cli.json_include_half_life,
+            branching: cli.json_include_branching,
+            stable_daughters: cli.show_stable_daughters,
+        }
+    }
+}
+
+/// Combined output of every source and the spectrum over all of them
+#[derive(Serialize)]
+struct JsonCombined<'a> {
+    sources: Vec<JsonSource<'a>>,
+    spectrum: Vec<JsonLine>,
+}
+
+/// Single line of the activity-weighted spectrum
+#[derive(Serialize)]
+struct JsonLine {
+    energy_kev: f32,
+    intensity_per_bq: f64,
+}
 
 /// Writes the nuclide data to a JSON file at the specified path.
-pub fn write(sources: &[Source], path: &Path, index: usize) -> Result<()> {
-    let f = create_file_with_fallback(path, "json", &format!("step_{index}.json"))?;
-    serde_json::to_writer_pretty(f, &sources).context("Unable to serialise to JSON")?;
+///
+/// Sources are written in descending order of activity so that the cumulative
+/// activity fractions may be used directly for sampling.
+pub fn write(
+    sources: &[Source],
+    path: &Path,
+    index: usize,
+    config: &JsonConfig,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let Some(mut f) = open_output(
+        path,
+        "json",
+        &format!("step_{index}.json"),
+        compress,
+        exists,
+    )?
+    else {
+        return Ok(());
+    };
+    write_to_writer(sources, config, &mut f)?;
+    f.finish()
+}
+
+/// Writes only the activity of each nuclide to `{name}.activity.json`
+///
+/// The object starts with the `interval` index and `total_activity_bq`,
+/// followed by every nuclide name and its activity [Bq] in descending order.
+pub fn write_activity_only(
+    sources: &[Source],
+    path: &Path,
+    index: usize,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let mut sorted = sources.iter().collect::<Vec<&Source>>();
+    sorted.sort_by(|a, b| b.fispact_activity.total_cmp(&a.fispact_activity));
+
+    let total = sources.iter().map(|s| s.fispact_activity).sum::<f64>();
+
+    let mut map = serde_json::Map::new();
+    map.insert("interval".to_string(), index.into());
+    map.insert("total_activity_bq".to_string(), total.into());
+    for s in sorted {
+        map.insert(s.fispact_name.clone(), s.fispact_activity.into());
+    }
+
+    let Some(mut f) = open_output(
+        path,
+        "activity.json",
+        &format!("step_{index}.activity.json"),
+        compress,
+        exists,
+    )?
+    else {
+        return Ok(());
+    };
+    serde_json::to_writer_pretty(&mut f, &map).context("Unable to write activities to JSON")?;
+    f.finish()
+}
+
+/// Writes a single source to its own JSON file
+///
+/// The `path` should already identify the nuclide, for example with
+/// `nuclide_output_path`, as only the extension is added here.
+pub fn write_single(
+    source: &Source,
+    path: &Path,
+    index: usize,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let Some(mut f) = open_output(
+        path,
+        "json",
+        &format!("step_{index}_{}.json", source.fispact_name),
+        compress,
+        exists,
+    )?
+    else {
+        return Ok(());
+    };
+    serde_json::to_writer_pretty(&mut f, source)
+        .with_context(|| format!("Unable to write {} to JSON", source.fispact_name))?;
+    f.finish()
+}
+
+/// Writes the nuclide data as JSON to any writer.
+pub fn write_to_writer<W: Write>(sources: &[Source], config: &JsonConfig, writer: W) -> Result<()> {
+    let json_sources = with_activity_fractions(sources, config);
+
+    match config.combined {
+        true => {
+            let combined = JsonCombined {
+                sources: json_sources,
+                spectrum: spectrum_per_bq(sources),
+            };
+            serde_json::to_writer_pretty(writer, &combined)
+        }
+        false => serde_json::to_writer_pretty(writer, &json_sources),
+    }
+    .context("Unable to serialise to JSON")?;
+
     Ok(())
 }
+
+/// Aggregate spectrum as particles emitted per decay of the whole mixture
+fn spectrum_per_bq(sources: &[Source]) -> Vec<JsonLine> {
+    let total = sources.iter().map(|s| s.fispact_activity).sum::<f64>();
+
+    aggregate_spectrum(sources)
+        .into_iter()
+        .map(|(energy_kev, rate)| JsonLine {
+            energy_kev,
+            intensity_per_bq: if total > 0.0 { rate / total } else { 0.0 },
+        })
+        .collect()
+}
+
+/// Sort by descending activity and attach the (cumulative) activity fractions
+fn with_activity_fractions<'a>(sources: &'a [Source], config: &JsonConfig) -> Vec<JsonSource<'a>> {
+    let mut sorted = sources.iter().collect::<Vec<&Source>>();
+    sorted.sort_by(|a, b| b.fispact_activity.total_cmp(&a.fispact_activity));
+
+    let total = sources.iter().map(|s| s.fispact_activity).sum::<f64>();
+    let mut cumulative = 0.0;
+
+    sorted
+        .into_iter()
+        .map(|source| {
+            let fraction = if total > 0.0 {
+                source.fispact_activity / total
+            } else {
+                0.0
+            };
+            cumulative += fraction;
+
+            JsonSource {
+                source,
+                activity_fraction: fraction,
+                cumulative_activity_fraction: cumulative,
+                photon_yield_per_second: source.photon_yield_per_second(),
+                norm_uncertainty: source.norm_uncertainty(),
+                relative_intensity: config
+                    .relative_intensity
+                    .then(|| source.relative_intensity()),
+                half_life_s: config
+                    .half_life
+                    .then(|| source.iaea_records.iter().map(|r| r.half_life).collect()),
+                decay_mode: config.half_life.then(|| {
+                    source
+                        .iaea_records
+                        .iter()
+                        .map(|r| r.decay_mode.display())
+                        .collect()
+                }),
+                branching_ratio: config
+                    .branching
+                    .then(|| source.iaea_records.iter().map(|r| r.branching).collect()),
+                stable_daughters: config.stable_daughters.then(|| source.stable_daughters()),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,35 @@
+//! Turn a FISPACT step into a monte-carlo source
+//!
+//! The `fisdef` binary is a thin command line interface over these modules.
+//! Custom MCNP decks may also be built directly, for example with the
+//! [mcnp::McnpOutput] builder, or from individual sections such as
+//! [mcnp::generate_activity_distribution].
+
+// crate modules
+pub mod batch;
+pub mod beta;
+pub mod cache;
+pub mod chain;
+pub mod check_iaea;
+pub mod cli;
+pub mod config;
+pub mod fileio;
+pub mod fispact_out;
+pub mod input;
+pub mod json;
+pub mod list_radtypes;
+pub mod manual;
+pub mod mcnp;
+pub mod mcnp_parser;
+pub mod per_nuclide;
+#[cfg(feature = "plot")]
+pub mod plot;
+pub mod prefetch;
+pub mod printlib;
+pub mod progress;
+pub mod record_set;
+pub mod report;
+pub mod source;
+pub mod spectrum;
+pub mod table;
+pub mod wrappers;
@@ -0,0 +1,85 @@
+//! Show which radiation types have decay data for a nuclide
+
+// internal
+use crate::cli::Cli;
+use crate::record_set::RecordSetExt;
+use crate::source::{NuclideCache, Source};
+use crate::wrappers::CliRadType;
+
+// neutronics toolbox
+use ntools::iaea;
+
+// external
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+/// Print the number of records and dominant line for every radiation type
+///
+/// Types without any data are still listed so that nothing looks missing.
+pub fn print(name: &str, cli: &Cli) -> Result<()> {
+    let nuclide = iaea::Nuclide::try_from(name.to_string())
+        .map_err(|_| anyhow!("\"{name}\" is not a recognised nuclide"))?;
+
+    println!("\n{:-<1$}", "", 46);
+    println!(
+        " {:<12} {:>12}   {:>18}",
+        "Type", "Records", "Dominant [keV]"
+    );
+    println!("{:-<1$}", "", 46);
+
+    let mut pool = NuclideCache::default();
+    for rad in CliRadType::value_variants() {
+        let mut source = Source {
+            fispact_name: name.to_string(),
+            fispact_activity: 1.0,
+            iaea_nuclide: nuclide.clone(),
+            iaea_records: Vec::new(),
+            from_chain: false,
+        };
+        source.find_records(
+            (*rad).into(),
+            cli.fetch,
+            cli.cache_dir.as_deref(),
+            cli.iaea_database_path.as_deref(),
+            &mut pool,
+        );
+        source.remove_unobserved_records();
+
+        let n = source.iaea_records.len();
+        let dominant = source
+            .iaea_records
+            .dominant()
+            .and_then(|r| r.energy)
+            .map_or("-".to_string(), |e| format!("{e:.3}"));
+
+        match n {
+            0 => println!(" {:<12} {:>12}   {:>18}", rad.name(), "(0 records)", "-"),
+            _ => println!(" {:<12} {:>12}   {:>18}", rad.name(), n, dominant),
+        }
+    }
+    println!();
+
+    // useful for finding out why an isomer has no records
+    let source = Source {
+        fispact_name: name.to_string(),
+        fispact_activity: 1.0,
+        iaea_nuclide: nuclide,
+        iaea_records: Vec::new(),
+        from_chain: false,
+    };
+    let energies = source
+        .parent_energies_available(cli.rad.into())
+        .iter()
+        .map(|e| format!("{e}"))
+        .collect::<Vec<String>>();
+    println!(
+        " Parent energies with {} data [keV]: {}\n",
+        cli.rad,
+        match energies.is_empty() {
+            true => "-".to_string(),
+            false => energies.join(", "),
+        }
+    );
+
+    Ok(())
+}
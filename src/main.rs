@@ -2,93 +2,675 @@
 #![doc(hidden)]
 
 // crate modules
-mod cli;
-mod json;
-mod mcnp;
-mod source;
-mod table;
-mod wrappers;
+#[cfg(feature = "plot")]
+use fisdef::plot;
+use fisdef::{
+    batch, beta, chain, check_iaea, cli, config, fileio, fispact_out, input, json, list_radtypes,
+    manual, mcnp, mcnp_parser, per_nuclide, prefetch, printlib, progress, report, source, spectrum,
+    table, wrappers,
+};
 
 // re-exports for convenience
-use cli::{Cli, MultiRange};
-use source::Source;
+use cli::{
+    Cli, Command, FileFormat, InterpolateArgs, ListRadtypesArgs, ManualArgs, MergeMcnpArgs,
+    MultiRange, NuclideSort, PlotArgs, PrefetchArgs, SchemaArgs, SummariseAllArgs,
+};
+use source::{cmp_by_activity, sort_sources, NuclideCache, Source, Sources};
+use wrappers::CliRadType;
 
 // neutronics toolbox
-use ntools::fispact::{self, Inventory};
+use ntools::fispact::{Interval, Inventory};
 use ntools::iaea::{self};
 use ntools::utils::{f, ValueExt};
 
 // standard lib
-use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 // other
-use anyhow::{bail, Context, Result};
-use clap::Parser;
-use log::{debug, info, trace, warn};
+use anyhow::{bail, Result};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use log::{debug, error, info, trace, warn};
+use notify::{RecursiveMode, Watcher};
 
 fn main() -> Result<()> {
     // set up the command line interface and logging
-    let cli = Cli::parse();
+    let args = config::with_config_args(std::env::args_os().collect())?;
+    let matches = Cli::command().get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches)?;
     cli::init_logging(&cli)?;
 
-    info!("Reading fispact JSON data");
-    let path: &Path = Path::new(&cli.path);
-    debug!("{:?}", path.display());
-    let inventory = fispact::read_json(path)?;
+    if cli.path.is_some() {
+        match matches.value_source("path") {
+            Some(ValueSource::EnvVariable) => debug!("Path taken from FISPACT_JSON_PATH"),
+            _ => debug!("Path taken from the command line"),
+        }
+    }
+
+    if let Some(path) = &cli.write_config {
+        config::write(&matches, path, cli.output_exists_action)?;
+    }
+
+    if (cli.endpoint_only || cli.spectral_shape.is_some()) && !cli.rad.is_beta() {
+        warn!("Beta options are ignored for {} sources", cli.rad);
+    }
+
+    if cli.mcnp_continuous_energy
+        && !matches!(cli.rad, CliRadType::BetaMinus | CliRadType::BetaPlus)
+    {
+        warn!(
+            "--mcnp-continuous-energy is ignored for {} sources",
+            cli.rad
+        );
+    }
+
+    if cli.mcnp_kcode {
+        warn!("--mcnp-kcode is only an approximate starting point for a criticality source");
+    }
+
+    if let Some(command) = &cli.command {
+        return match command {
+            Command::Interpolate(args) => interpolate(args, &cli),
+            Command::Manual(args) => manual(args, &cli),
+            Command::Prefetch(args) => prefetch(args, &cli),
+            Command::Schema(args) => schema(args, &cli),
+            Command::ListRadtypes(args) => list_radtypes(args, &cli),
+            Command::MergeMcnp(args) => merge_mcnp(args, &cli),
+            Command::Plot(args) => plot(args, &cli),
+            Command::CheckIaea(args) => check_iaea::run(args, &cli),
+            Command::SummariseAll(args) => summarise_all(args, &cli),
+        };
+    }
+
+    // stdin is used if no path is given, so '-' here is just a placeholder
+    let path = Path::new(cli.path.as_deref().unwrap_or("-"));
+
+    if cli.watch {
+        if cli.stdin || path == Path::new("-") {
+            bail!("--watch requires a file path rather than stdin")
+        }
+        return watch(path, &cli);
+    }
+
+    convert(path, &cli)
+}
+
+/// Run the full conversion from FISPACT inventory to output files
+fn convert(path: &Path, cli: &Cli) -> Result<()> {
+    if let Some(dir) = &cli.iaea_database_path {
+        let resolved = dir.canonicalize().unwrap_or(dir.clone());
+        debug!("IAEA database path: {}", resolved.display());
+    }
+
+    let inventory = load_inventory(path, cli)?;
+    check_inventory(&inventory, cli)?;
 
     info!("Table of FISPACT intervals");
     fispact_summary(&inventory);
 
     // short-circuit if no outputs given
-    if !cli.mcnp && !cli.json && !cli.text {
+    if !outputs_requested(cli) {
         debug!("No outputs requested");
         return Ok(());
     }
 
+    if let Some(time_s) = cli.interpolate_at_time {
+        info!("Interpolating sources to t = {} s", time_s.sci(2, 2));
+        return write_interpolated(&inventory, time_s, cli);
+    }
+
     info!("Parsing user input to explicit interval indices");
     let index_list = index_list(&cli.index, &inventory)?;
 
+    // decay data are shared by every interval
+    let mut pool = NuclideCache::default();
+
+    let mut progress = cli
+        .progress_json
+        .as_deref()
+        .map(|path| progress::Progress::new(path, index_list.len(), cli.output_exists_action))
+        .transpose()?
+        .flatten();
+
+    let mut processed = Vec::new();
     for index in index_list {
-        process_interval(&inventory, index, &cli)?;
+        let sources = process_interval(&inventory, index, cli, &mut pool)?;
+
+        if let Some(progress) = progress.as_mut() {
+            match (&sources, cli.skip_empty_intervals) {
+                (None, true) => progress.skip(),
+                _ => {
+                    let output = sources.as_ref().map(|_| output_path(cli, index));
+                    progress.update(index, output.as_deref())?;
+                }
+            }
+        }
+
+        if let Some(sources) = sources {
+            if cli.report || cli.output_per_nuclide {
+                processed.push((index, sources));
+            }
+        }
+    }
+
+    if cli.output_per_nuclide {
+        debug!("Writing activity history of each nuclide");
+        let path = output_path(cli, 0);
+        let dir = path.parent().unwrap_or(Path::new("."));
+        per_nuclide::per_nuclide_output(
+            &processed,
+            &inventory,
+            dir,
+            cli.compress_output,
+            cli.output_exists_action,
+        )?;
+    }
+
+    if cli.report {
+        debug!("Writing summary report");
+        report::write(&inventory, &processed, cli)?;
     }
 
     Ok(())
 }
 
-fn process_interval(inventory: &Inventory, index: usize, cli: &Cli) -> Result<()> {
+/// Convert the file, then again every time it is modified until interrupted
+fn watch(path: &Path, cli: &Cli) -> Result<()> {
+    if let Err(e) = convert(path, cli) {
+        error!("{e}");
+    }
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(path, RecursiveMode::NonRecursive)?;
+    info!("Watching {} for changes (Ctrl-C to exit)", path.display());
+
+    for event in rx {
+        match event {
+            Ok(event) if event.kind.is_modify() => {
+                println!("[WATCH] File changed, reprocessing...");
+                if let Err(e) = convert(path, cli) {
+                    error!("{e}");
+                }
+            }
+            Ok(_) => (),
+            Err(e) => warn!("{e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the interpolation workflow for a single arbitrary time
+fn interpolate(args: &InterpolateArgs, cli: &Cli) -> Result<()> {
+    let inventory = load_inventory(Path::new(&args.path), cli)?;
+    check_inventory(&inventory, cli)?;
+
+    info!("Table of FISPACT intervals");
+    fispact_summary(&inventory);
+
+    if !outputs_requested(cli) {
+        debug!("No outputs requested");
+        return Ok(());
+    }
+
+    info!("Interpolating sources to t = {} s", args.time.sci(2, 2));
+    let mut pool = NuclideCache::default();
+    if let Some((sources, index)) =
+        interpolate_sources(&inventory, args.time, Interpolation::Linear, cli, &mut pool)?
+    {
+        let mut path = output_path(cli, index);
+        path.set_file_name(f!("{}_interpolated", cli.output_name()));
+        write_outputs(&sources, path.as_path(), Some(&inventory), index, cli)?;
+    } else {
+        info!("No relevant decay data found");
+    }
+
+    Ok(())
+}
+
+/// Scale all activities to a target total, returning the original total
+fn scale_activities(sources: &[Source], target: f64) -> (Vec<Source>, f64) {
+    let total = sources.iter().map(|s| s.fispact_activity).sum::<f64>();
+    let scale = if total > 0.0 { target / total } else { 1.0 };
+    debug!("Scaling activities by {}", scale.sci(5, 2));
+
+    let scaled = sources
+        .iter()
+        .cloned()
+        .map(|mut s| {
+            s.fispact_activity *= scale;
+            s
+        })
+        .collect();
+
+    (scaled, total)
+}
+
+/// Generate outputs from manually defined decay lines
+fn manual(args: &ManualArgs, cli: &Cli) -> Result<()> {
+    info!("Reading manual source definition");
+    let mut sources = manual::read(Path::new(&args.path))?;
+
+    if !outputs_requested(cli) {
+        debug!("No outputs requested");
+        return Ok(());
+    }
+
+    for s in sources.iter_mut() {
+        s.remove_unobserved_records();
+        s.sort_records(&cli.sort);
+    }
+    sort_sources(&mut sources, cli.sort_nuclides);
+
+    let mut path = output_path(cli, 0);
+    path.set_file_name(f!("{}_manual", cli.output_name()));
+    write_outputs(&sources, path.as_path(), None, 0, cli)
+}
+
+/// Fetch and cache decay data for every nuclide in the inventory
+fn prefetch(args: &PrefetchArgs, cli: &Cli) -> Result<()> {
+    let Some(cache_dir) = &cli.cache_dir else {
+        bail!("A cache directory must be provided with --cache-dir")
+    };
+
+    let inventory = load_inventory(Path::new(&args.path), cli)?;
+    prefetch::prefetch_all(&inventory, cli.rad.into(), cache_dir);
+    Ok(())
+}
+
+/// Show the decay data available for a nuclide under each radiation type
+fn list_radtypes(args: &ListRadtypesArgs, cli: &Cli) -> Result<()> {
+    list_radtypes::print(&args.nuclide, cli)
+}
+
+/// Plot the source spectrum of a single interval
+#[cfg(feature = "plot")]
+fn plot(args: &PlotArgs, cli: &Cli) -> Result<()> {
+    let inventory = load_inventory(Path::new(&args.path), cli)?;
+    let Some(last) = inventory.intervals.len().checked_sub(1) else {
+        bail!("No intervals found in {}", args.path)
+    };
+
+    let index = args.interval.unwrap_or(last);
+    if index > last {
+        bail!("Interval {index} is out of range, the final interval is {last}")
+    }
+
+    let mut pool = NuclideCache::default();
+    let Ok(sources) = get_sources(&inventory, index, cli, &mut pool) else {
+        bail!("No relevant decay data found in interval {index}")
+    };
+
+    let path = output_path(cli, index).with_extension("png");
+    info!("Plotting spectrum to {}", path.display());
+    plot::draw(&sources, &path)
+}
+
+/// Plotting is unavailable without the `plot` feature
+#[cfg(not(feature = "plot"))]
+fn plot(_: &PlotArgs, _: &Cli) -> Result<()> {
+    bail!("fisdef was built without plotting, rebuild with '--features plot'")
+}
+
+/// Combine the source distributions of several MCNP files into one
+fn merge_mcnp(args: &MergeMcnpArgs, cli: &Cli) -> Result<()> {
+    let sources = args
+        .paths
+        .iter()
+        .map(|p| {
+            info!("Reading MCNP source from {p}");
+            mcnp_parser::read(Path::new(p))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut particles = sources
+        .iter()
+        .filter_map(|s| s.particle.as_deref())
+        .collect::<Vec<_>>();
+    particles.sort();
+    particles.dedup();
+    if particles.len() > 1 {
+        warn!("Merging sources of different particles: {particles:?}");
+    }
+
+    let config = mcnp::McnpConfig::from(cli);
+    let cards = mcnp::merged_cards(&sources, &config);
+
+    let path = run_output_path(cli, "merged");
+    let Some(mut f) = fileio::open_output(
+        &path,
+        "i",
+        "merged.i",
+        cli.compress_output,
+        cli.output_exists_action,
+    )?
+    else {
+        return Ok(());
+    };
+    f.write_all(cards.as_bytes())?;
+    f.finish()
+}
+
+/// Write the interval totals of every matching file to a combined CSV
+fn summarise_all(args: &SummariseAllArgs, cli: &Cli) -> Result<()> {
+    let records = batch::summarise_directory(&args.dir, &args.pattern)?;
+
+    let path = run_output_path(cli, "summary");
+    debug!("Summarised {} intervals", records.len());
+    let Some(mut f) = fileio::open_output(
+        &path,
+        "csv",
+        "summary.csv",
+        cli.compress_output,
+        cli.output_exists_action,
+    )?
+    else {
+        return Ok(());
+    };
+    batch::write_csv(&records, &mut f)?;
+    f.finish()
+}
+
+/// Print or write the JSON Schema for the JSON output
+fn schema(args: &SchemaArgs, cli: &Cli) -> Result<()> {
+    let schema = json::schema()?;
+
+    match &args.json_schema_output {
+        Some(path) => {
+            if let Some(mut f) = fileio::open_named(path, cli.output_exists_action)? {
+                f.write_all(schema.as_bytes())?;
+                f.finish()?;
+            }
+        }
+        None => println!("{schema}"),
+    }
+
+    Ok(())
+}
+
+/// Check if any output files, or anything beyond the summary, were requested
+fn outputs_requested(cli: &Cli) -> bool {
+    cli.list_decay_modes
+        || cli.stats
+        || cli.mcnp
+        || cli.mcnp_erg_card
+        || cli.json
+        || cli.json_combined
+        || cli.json_per_nuclide_files
+        || cli.json_activity_only
+        || cli.text
+        || cli.report
+        || cli.output_per_nuclide
+        || cli.spectrum_bins.is_some()
+}
+
+/// Write the outputs for an interval, returning the sources on success
+fn process_interval(
+    inventory: &Inventory,
+    index: usize,
+    cli: &Cli,
+    pool: &mut NuclideCache,
+) -> Result<Option<Vec<Source>>> {
     info!("Generating sources from interval {index}");
-    if let Some(sources) = get_sources(inventory, index, cli) {
-        let path = output_path(cli, index);
+    let sources = match get_sources(inventory, index, cli, pool) {
+        Ok(sources) => sources,
+        Err(_) if cli.skip_empty_intervals => return Ok(None),
+        Err(EmptyReason::NoUnstableNuclides) => {
+            info!("No relevant decay data found, as there are no unstable nuclides");
+            return Ok(None);
+        }
+        Err(EmptyReason::NoIaeaRecords) => {
+            info!(
+                "No relevant decay data found, as no nuclides have {} records",
+                cli.rad
+            );
+            return Ok(None);
+        }
+        Err(EmptyReason::AllFiltered) => {
+            info!("No relevant decay data found, as every record was filtered out");
+            return Ok(None);
+        }
+    };
+
+    if cli.stats {
+        print_stats(&sources);
+    }
+
+    let path = output_path(cli, index);
+    write_outputs(&sources, path.as_path(), Some(inventory), index, cli)?;
+    Ok(Some(sources))
+}
+
+/// Print the energy statistics of every nuclide
+fn print_stats(sources: &[Source]) {
+    println!(
+        "{:<8} {:>8} {:>12} {:>12} {:>12} {:>12}",
+        "Nuclide", "Records", "Mean [keV]", "Median [keV]", "Mode [keV]", "Total [%]"
+    );
 
-        if cli.json {
-            info!("Writing to JSON");
-            json::write(&sources, path.as_path(), index)?;
+    for s in sources {
+        let Some(stats) = source::record_set_stats(&s.iaea_records) else {
+            continue;
+        };
+        println!(
+            "{:<8} {:>8} {:>12.3} {:>12.3} {:>12.3} {:>12.4}",
+            s.fispact_name,
+            stats.n_records,
+            stats.mean_kev,
+            stats.median_kev,
+            stats.mode_kev,
+            stats.total_intensity
+        );
+    }
+}
+
+/// Write every requested output format for a set of sources
+fn write_outputs(
+    sources: &[Source],
+    path: &Path,
+    inventory: Option<&Inventory>,
+    index: usize,
+    cli: &Cli,
+) -> Result<()> {
+    // rescale every output to the requested total activity
+    let (scaled, scaling) = match cli.mcnp_normalize_to {
+        Some(target) => {
+            let (scaled, original) = scale_activities(sources, target);
+            (scaled, Some((original, target)))
         }
+        None => (sources.to_vec(), None),
+    };
+    let sources = scaled.as_slice();
+    let exists = cli.output_exists_action;
 
-        if cli.mcnp {
-            info!("Writing to MCNP");
-            mcnp::write(&sources, path.as_path(), index, cli.id)?;
+    if cli.json || cli.json_combined {
+        debug!("Writing to JSON");
+        let config = json::JsonConfig::from(cli);
+        json::write(sources, path, index, &config, cli.compress_output, exists)?;
+    }
+
+    if cli.json_per_nuclide_files {
+        debug!("Writing each nuclide to JSON");
+        for source in sources {
+            let path = nuclide_output_path(path, &source.fispact_name);
+            json::write_single(source, &path, index, cli.compress_output, exists)?;
         }
+    }
 
-        if cli.text {
-            info!("Writing to text file");
-            let table = table::Table::new(&sources);
-            table.write(path.as_path())?;
+    if cli.json_activity_only {
+        debug!("Writing activities to JSON");
+        json::write_activity_only(sources, path, index, cli.compress_output, exists)?;
+    }
+
+    // spread beta lines over the spectral shape for MCNP sources only
+    let shaped = match &cli.spectral_shape {
+        Some(shape_path) if cli.rad.is_beta() && (cli.mcnp || cli.mcnp_erg_card) => {
+            let shape = beta::read_shape(shape_path)?;
+            sources
+                .iter()
+                .map(|s| beta::apply_shape(s, &shape))
+                .collect::<Vec<Source>>()
         }
-    } else {
-        info!("No relevant decay data found");
+        _ => sources.to_vec(),
+    };
+
+    if cli.mcnp {
+        debug!("Writing to MCNP");
+        let sources = shaped.as_slice();
+        let mut config = mcnp::McnpConfig::from(cli);
+        config.scaling = scaling;
+        mcnp::write(
+            sources,
+            path,
+            inventory,
+            index,
+            &config,
+            cli.compress_output,
+            exists,
+        )?;
+
+        if cli.mcnp_verify || cli.mcnp_debug_totals {
+            let cards = mcnp::generate_mcnp_cards(sources, &config);
+
+            if cli.mcnp_verify {
+                for warning in mcnp::validate_mcnp_cards(&cards, &config) {
+                    warn!("MCNP check: {warning}");
+                }
+            }
+
+            if cli.mcnp_debug_totals {
+                for (total, ok) in mcnp::sp_totals(sources, &cards, &config) {
+                    eprintln!("{total}");
+                    if !ok {
+                        warn!("MCNP check: {total}");
+                    }
+                }
+            }
+        }
+    }
+
+    if cli.mcnp_erg_card {
+        debug!("Writing MCNP energy distributions");
+        let config = mcnp::McnpConfig::from(cli);
+        mcnp::write_erg(&shaped, path, index, &config, cli.compress_output, exists)?;
+    }
+
+    if cli.text {
+        debug!("Writing to text file");
+        let config = table::TableConfig::from(cli);
+        let table = table::Table::new(sources, &config);
+        table.write(path, cli.compress_output, exists)?;
+    }
+
+    if let Some(n_bins) = cli.spectrum_bins {
+        debug!("Writing binned spectrum");
+        let spectrum = spectrum::build_spectrum(sources, n_bins);
+        spectrum::write(&spectrum, path, index, cli.compress_output, exists)?;
+
+        if cli.mcnp {
+            let (_, width) = spectrum::binning(sources, n_bins).unwrap_or_default();
+            mcnp::write_spectrum(
+                &spectrum,
+                width,
+                path,
+                index,
+                cli.id,
+                cli.compress_output,
+                exists,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the FISPACT inventory in whichever format was requested
+fn load_inventory(path: &Path, cli: &Cli) -> Result<Inventory> {
+    if cli.stdin || path == Path::new("-") {
+        info!("Reading fispact data from stdin");
+        let stdin = std::io::stdin().lock();
+        return match cli.format {
+            FileFormat::Json => {
+                input::read_inventory_reader(stdin, cli.input_format, cli.decompress)
+            }
+            FileFormat::FispactOut => fispact_out::from_str(&std::io::read_to_string(stdin)?),
+            FileFormat::Printlib => printlib::from_str(&std::io::read_to_string(stdin)?),
+        };
+    }
+
+    info!("Reading fispact data");
+    debug!("{:?}", path.display());
+
+    match cli.format {
+        FileFormat::Json => input::read_inventory(path, cli.input_format, cli.decompress),
+        FileFormat::FispactOut => fispact_out::read(path),
+        FileFormat::Printlib => printlib::read(path),
+    }
+}
+
+/// Log any problems with the inventory, failing on them in strict mode
+fn check_inventory(inventory: &Inventory, cli: &Cli) -> Result<()> {
+    let problems = validate_inventory(inventory);
+    for p in &problems {
+        warn!("{p}");
+    }
+
+    if cli.strict && !problems.is_empty() {
+        bail!(
+            "{} problem(s) found in the FISPACT inventory",
+            problems.len()
+        )
     }
 
     Ok(())
 }
 
+/// Check every interval for values that would make the outputs meaningless
+fn validate_inventory(inventory: &Inventory) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (i, interval) in inventory.intervals.iter().enumerate() {
+        if interval.irradiation_time < 0.0 {
+            problems.push(f!(
+                "Interval {i} has negative irradiation time ({} s)",
+                interval.irradiation_time
+            ));
+        }
+
+        if interval.cooling_time < 0.0 {
+            problems.push(f!(
+                "Interval {i} has negative cooling time ({} s)",
+                interval.cooling_time
+            ));
+        }
+
+        if interval.mass <= 0.0 {
+            problems.push(f!(
+                "Interval {i} has non-positive mass ({} g)",
+                interval.mass
+            ));
+        }
+
+        if interval.activity < 0.0 {
+            problems.push(f!(
+                "Interval {i} has negative activity ({} Bq)",
+                interval.activity
+            ));
+        }
+    }
+
+    problems
+}
+
 /// Summarise intervals in the file
 fn fispact_summary(inventory: &Inventory) {
     struct Record {
         irrad_time: f64,
         cool_time: f64,
         total_time: f64,
+        n_nuclides: usize,
         mass: f64,
         dose: f64,
         activity: f64,
@@ -101,31 +683,51 @@ fn fispact_summary(inventory: &Inventory) {
             irrad_time: i.irradiation_time,
             cool_time: i.cooling_time,
             total_time: i.irradiation_time + i.cooling_time,
+            n_nuclides: count_unstable(i),
             mass: i.mass,
             dose: i.dose.rate,
             activity: i.activity,
         })
         .collect::<Vec<Record>>();
 
+    // FISPACT cooling times are already cumulative, so the last is the total
+    let final_cool = records.last().map_or(0.0, |r| r.cool_time);
+
     println!("\n{:-<1$}", "", 71);
-    println!("           Interval Time [s]                  Interval totals");
-    println!("Index   Irrad     Cool    Total      Mass [g]  Dose [uSv/hr]   Act [Bq]");
+    println!(" Idx   Irrad    Cool   Total Phase   Cum  Nucl     Mass    Dose     Act");
+    println!("         [s]     [s]     [s]         [%]            [g] [uSv/h]    [Bq]");
     println!("{:-<1$}", "", 71);
 
     for (i, r) in records.iter().enumerate() {
+        let phase = if r.irrad_time > r.cool_time {
+            "IRRAD"
+        } else {
+            "COOL"
+        };
+        let fraction = match final_cool > 0.0 {
+            true => 100.0 * r.cool_time / final_cool,
+            false => 0.0,
+        };
+
         println!(
-            " {i:<3}   {} {} {}    {}     {}     {}",
-            r.irrad_time.sci(2, 2),
-            r.cool_time.sci(2, 2),
-            r.total_time.sci(2, 2),
-            r.mass.sci(2, 2),
-            (r.dose * 1e6).sci(2, 2),
-            r.activity.sci(2, 2),
+            " {i:<3} {} {} {} {phase:<5} {fraction:>5.1} {:>5}  {} {} {}",
+            r.irrad_time.sci(1, 2),
+            r.cool_time.sci(1, 2),
+            r.total_time.sci(1, 2),
+            r.n_nuclides,
+            r.mass.sci(1, 2),
+            (r.dose * 1e6).sci(1, 2),
+            r.activity.sci(1, 2),
         )
     }
     println!();
 }
 
+/// Number of unstable nuclides in an interval
+fn count_unstable(interval: &Interval) -> usize {
+    interval.unstable_nuclides().len()
+}
+
 /// List of explicit index for each valid interval
 fn index_list(user_idx: &MultiRange, inventory: &Inventory) -> Result<Vec<usize>> {
     let n = inventory.intervals.len();
@@ -141,6 +743,15 @@ fn index_list(user_idx: &MultiRange, inventory: &Inventory) -> Result<Vec<usize>
     index_list.sort();
     index_list.dedup();
 
+    // lists with large gaps are more likely to be a mistyped range
+    if matches!(user_idx, MultiRange::List(_)) && has_large_gap(&index_list) {
+        warn!(
+            "Hint: did you mean {}-{}? Use a range for consecutive indices.",
+            index_list[0],
+            index_list[index_list.len() - 1]
+        );
+    }
+
     // validate whatever is left
     index_list.retain(|i| *i < n);
     if index_list.is_empty() {
@@ -151,6 +762,25 @@ fn index_list(user_idx: &MultiRange, inventory: &Inventory) -> Result<Vec<usize>
     Ok(index_list)
 }
 
+/// Why an interval has no sources to write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EmptyReason {
+    /// FISPACT found no unstable nuclides known to the IAEA data
+    NoUnstableNuclides,
+    /// None of the nuclides have records for the chosen radiation type
+    NoIaeaRecords,
+    /// Records were found, but every one was removed by the filters
+    AllFiltered,
+}
+
+/// Largest gap between consecutive listed indices before hinting at a range
+const MAX_INDEX_GAP: usize = 5;
+
+/// Whether any consecutive indices of a sorted list are far apart
+fn has_large_gap(indices: &[usize]) -> bool {
+    indices.windows(2).any(|w| w[1] - w[0] > MAX_INDEX_GAP)
+}
+
 /// Turn FISPACT nuclide names into IAEA nuclide structs
 fn parse_nuclides(inventory: &Inventory, index: usize) -> Option<Vec<Source>> {
     // collect all unstable nuclides that also exist in the IAEA data
@@ -164,6 +794,7 @@ fn parse_nuclides(inventory: &Inventory, index: usize) -> Option<Vec<Source>> {
                     fispact_activity: n.activity,
                     iaea_nuclide: nuclide,
                     iaea_records: Vec::new(),
+                    from_chain: false,
                 })
             } else {
                 debug!("Could not convert {n:?} to nuclide, skipping...");
@@ -193,15 +824,103 @@ fn parse_nuclides(inventory: &Inventory, index: usize) -> Option<Vec<Source>> {
     Some(sources)
 }
 
-fn get_sources(inventory: &Inventory, index: usize, cli: &Cli) -> Option<Vec<Source>> {
+fn get_sources(
+    inventory: &Inventory,
+    index: usize,
+    cli: &Cli,
+    pool: &mut NuclideCache,
+) -> Result<Vec<Source>, EmptyReason> {
     // start mapping fispact to iaea nuclides
-    let mut sources = parse_nuclides(inventory, index)?;
+    let mut sources = parse_nuclides(inventory, index).ok_or(EmptyReason::NoUnstableNuclides)?;
+
+    // drop anything below the activity threshold, absolute or relative to max
+    if let Some(floor) = activity_floor(&sources, cli) {
+        sources.retain(|s| s.fispact_activity >= floor);
+        if sources.is_empty() {
+            return Err(EmptyReason::AllFiltered);
+        }
+    }
+
+    if cli.chain_depth > 1 {
+        chain::expand_decay_chain(
+            &mut sources,
+            cli.chain_depth,
+            cli.fetch,
+            cli.cache_dir.as_deref(),
+            cli.iaea_database_path.as_deref(),
+            pool,
+        );
+    }
 
     // fill with records for the relevant decay type
+    let mut any_records = false;
     for s in sources.iter_mut() {
-        s.find_records(cli.rad.into(), cli.fetch);
+        match cli.all_states {
+            true => s.find_records_all_states(
+                cli.rad.into(),
+                cli.fetch,
+                cli.cache_dir.as_deref(),
+                cli.iaea_database_path.as_deref(),
+                pool,
+            ),
+            false => s.find_records(
+                cli.rad.into(),
+                cli.fetch,
+                cli.cache_dir.as_deref(),
+                cli.iaea_database_path.as_deref(),
+                pool,
+            ),
+        }
+
+        any_records |= !s.iaea_records.is_empty();
+
+        if cli.list_decay_modes && !s.iaea_records.is_empty() {
+            println!("{:<8} {}", s.fispact_name, s.decay_modes().join(", "));
+        }
+
+        if let Some(modes) = &cli.decay_mode_filter {
+            s.retain_decay_modes(modes);
+        }
+
         s.remove_unobserved_records();
+        if cli.endpoint_only && cli.rad.is_beta() {
+            s.keep_endpoint_only();
+        }
+        s.dedup_by_energy_proximity(cli.energy_dedup_tolerance);
         s.sort_records(&cli.sort);
+
+        if let Some(max) = cli.max_records {
+            s.truncate_records(max);
+        }
+    }
+
+    // keep only nuclides with a line near the energy of interest
+    if let Some(energy) = cli.energy_query {
+        sources.retain(|s| {
+            let found = s.intensity_at_energy(energy, cli.energy_tolerance);
+            if found.is_none() {
+                trace!(
+                    "No {} line within {} keV of {energy} keV",
+                    s.fispact_name,
+                    cli.energy_tolerance
+                );
+            }
+            found.is_some()
+        });
+    }
+
+    // report anything with no remaining records, e.g. only stable products
+    let n_empty = sources
+        .iter()
+        .filter(|s| s.iaea_records.is_empty())
+        .inspect(|s| trace!("No {} records for {}", cli.rad, s.fispact_name))
+        .count();
+    if n_empty > 0 {
+        debug!(
+            "Dropped {n_empty} of {} nuclides with no {} records",
+            sources.len(),
+            cli.rad
+        );
     }
 
     // filter out anything with no remaining records
@@ -209,13 +928,225 @@ fn get_sources(inventory: &Inventory, index: usize, cli: &Cli) -> Option<Vec<Sou
 
     // if none of them had decay data, then sources will be empty
     if sources.is_empty() {
-        return None;
+        return match any_records {
+            true => Err(EmptyReason::AllFiltered),
+            false => Err(EmptyReason::NoIaeaRecords),
+        };
     }
 
-    // sort the sources by name because why not
-    sources.sort_by_key(|s| s.fispact_name.clone());
+    // keep only the most active if asked, which are then ordered by activity
+    let order = match cli.top_by_activity {
+        Some(n) => {
+            sources.sort_by(|a, b| cmp_by_activity(b, a));
+            sources.truncate(n);
+            NuclideSort::Activity
+        }
+        None => cli.sort_nuclides,
+    };
 
-    Some(sources)
+    // sort the sources by name unless told otherwise
+    sort_sources(&mut sources, order);
+    debug!("{}", Sources(sources.clone()));
+
+    Ok(sources)
+}
+
+/// Interpolation of nuclide activities between the bounding intervals
+#[derive(Debug, Clone, Copy)]
+enum Interpolation {
+    /// Linear in activity
+    Linear,
+    /// Linear in the log of activity, or linear where either side is zero
+    LogLinear,
+}
+
+impl Interpolation {
+    /// Activity a `fraction` of the way from `a1` to `a2`
+    fn activity(self, a1: f64, a2: f64, fraction: f64) -> f64 {
+        match self {
+            Self::LogLinear if a1 > 0.0 && a2 > 0.0 => {
+                (a1.ln() + fraction * (a2.ln() - a1.ln())).exp()
+            }
+            _ => a1 + fraction * (a2 - a1),
+        }
+    }
+}
+
+/// Interpolate nuclide sources to an arbitrary time [s]
+///
+/// The time of each interval is the irradiation + cooling time. Returns the
+/// synthetic sources along with the index of the lower bounding interval.
+fn interpolate_sources(
+    inventory: &Inventory,
+    time_s: f64,
+    mode: Interpolation,
+    cli: &Cli,
+    pool: &mut NuclideCache,
+) -> Result<Option<(Vec<Source>, usize)>> {
+    let (lower, upper, fraction) = bounding_intervals(inventory, time_s)?;
+
+    if lower == upper {
+        debug!("{} s matches interval {upper} exactly", time_s.sci(2, 2));
+        return Ok(get_sources(inventory, upper, cli, pool)
+            .ok()
+            .map(|s| (s, upper)));
+    }
+
+    let activities = interpolate_at(inventory, lower, upper, fraction, mode);
+
+    // decay data for nuclides from either side of the interval
+    let mut sources = get_sources(inventory, lower, cli, pool).unwrap_or_default();
+    for s in get_sources(inventory, upper, cli, pool).unwrap_or_default() {
+        if !sources.iter().any(|e| e.fispact_name == s.fispact_name) {
+            sources.push(s);
+        }
+    }
+
+    sources.retain_mut(
+        |s| match activities.iter().find(|(name, _)| *name == s.fispact_name) {
+            Some((_, activity)) => {
+                s.fispact_activity = *activity;
+                true
+            }
+            None => false,
+        },
+    );
+
+    if sources.is_empty() {
+        return Ok(None);
+    }
+
+    sort_sources(&mut sources, cli.sort_nuclides);
+    Ok(Some((sources, lower)))
+}
+
+/// Lower and upper intervals bounding a time [s], and the fraction between
+fn bounding_intervals(inventory: &Inventory, time_s: f64) -> Result<(usize, usize, f64)> {
+    let times = inventory
+        .intervals
+        .iter()
+        .map(|i| i.irradiation_time + i.cooling_time)
+        .collect::<Vec<f64>>();
+
+    let Some(upper) = times.iter().position(|t| *t >= time_s) else {
+        bail!("{} s is beyond the final interval", time_s.sci(2, 2))
+    };
+
+    if times[upper] == time_s {
+        return Ok((upper, upper, 0.0));
+    }
+
+    if upper == 0 {
+        bail!("{} s is before the first interval", time_s.sci(2, 2))
+    }
+
+    let lower = upper - 1;
+    let fraction = (time_s - times[lower]) / (times[upper] - times[lower]);
+    Ok((lower, upper, fraction))
+}
+
+/// Interpolate the activity of every nuclide between two intervals
+///
+/// Nuclides missing from either interval have zero activity there, and only
+/// those with a positive interpolated activity are kept.
+fn interpolate_at(
+    inventory: &Inventory,
+    lower: usize,
+    upper: usize,
+    fraction: f64,
+    mode: Interpolation,
+) -> Vec<(String, f64)> {
+    let activities = |index: usize| {
+        inventory.intervals[index]
+            .unstable_nuclides()
+            .into_iter()
+            .map(|n| (n.name(), n.activity))
+            .collect::<Vec<(String, f64)>>()
+    };
+    let lower_activities = activities(lower);
+    let upper_activities = activities(upper);
+
+    let time = |i: &Interval| (i.irradiation_time + i.cooling_time).sci(2, 2);
+    match mode {
+        Interpolation::Linear => {
+            warn!("Linear interpolation of exponential decay is only approximate")
+        }
+        Interpolation::LogLinear => {
+            warn!("Log-linear interpolation between intervals is only approximate")
+        }
+    }
+    debug!(
+        "Interpolating between interval {lower} ({} s) and {upper} ({} s)",
+        time(&inventory.intervals[lower]),
+        time(&inventory.intervals[upper])
+    );
+
+    let mut names = lower_activities
+        .iter()
+        .chain(&upper_activities)
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<String>>();
+    names.sort();
+    names.dedup();
+
+    let find = |activities: &[(String, f64)], name: &str| {
+        activities
+            .iter()
+            .find(|(n, _)| n == name)
+            .map_or(0.0, |(_, a)| *a)
+    };
+
+    let mut interpolated = Vec::with_capacity(names.len());
+    for name in names {
+        let a1 = find(&lower_activities, &name);
+        let a2 = find(&upper_activities, &name);
+        let activity = mode.activity(a1, a2, fraction);
+        debug!(
+            "   {name:<6} {} Bq -> {} Bq -> {} Bq",
+            a1.sci(5, 2),
+            activity.sci(5, 2),
+            a2.sci(5, 2)
+        );
+
+        if activity > 0.0 {
+            interpolated.push((name, activity));
+        }
+    }
+
+    interpolated
+}
+
+/// Write outputs for sources log-linearly interpolated to a time [s]
+fn write_interpolated(inventory: &Inventory, time_s: f64, cli: &Cli) -> Result<()> {
+    let mut pool = NuclideCache::default();
+    let Some((sources, index)) =
+        interpolate_sources(inventory, time_s, Interpolation::LogLinear, cli, &mut pool)?
+    else {
+        info!("No relevant decay data found");
+        return Ok(());
+    };
+
+    let mut path = output_path(cli, index);
+    path.set_file_name(f!("{}_interpolated", cli.output_name()));
+    write_outputs(&sources, path.as_path(), Some(inventory), index, cli)
+}
+
+/// Minimum activity [Bq] for a nuclide to be included, if any
+fn activity_floor(sources: &[Source], cli: &Cli) -> Option<f64> {
+    let relative = cli.activity_floor_fraction.map(|fraction| {
+        let max = sources
+            .iter()
+            .map(|s| s.fispact_activity)
+            .fold(0.0, f64::max);
+        let floor = max * fraction;
+        debug!("Activity floor: {} Bq (--min-activity)", floor.sci(5, 2));
+        floor
+    });
+
+    match (cli.min_activity, relative) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
 }
 
 /// Sanitise the output given and append interval index
@@ -223,36 +1154,52 @@ pub fn output_path(cli: &Cli, index: usize) -> PathBuf {
     let mut path = PathBuf::from(&cli.output);
 
     // take the file name provided
-    let name = path
-        .file_stem()
-        .and_then(|name| name.to_str())
-        .unwrap_or("step");
+    let name = cli.output_name();
     trace!("Found the name \"{name}\"");
 
-    // append the mesh tally number to the name
-    path.set_file_name(f!("{name}_{index}"));
+    // append the mesh tally number to the name, zero-padded if requested
+    let width = cli.output_prefix_pad;
+    path.set_file_name(f!("{name}_{index:0>width$}"));
 
     trace!("Output prefix: {:?}", path.file_name().unwrap());
     path
 }
 
-/// Try to create a file, including all dirs, with a default to fallback on
-fn create_file_with_fallback(path: &Path, extension: &str, default: &str) -> Result<File> {
-    let mut p = path.to_path_buf();
-
-    // Ensure all parent directories exist
-    if let Some(parent) = path.parent() {
-        if let Err(e) = fs::create_dir_all(parent) {
-            warn!("{e}. Falling back to working directory.");
-            p = p.file_name().expect("No file name provided").into();
-        }
+/// Path of an output written once per run rather than per interval
+///
+/// A custom '--output' is used as the file name, keeping any directory, while
+/// the default name gains a suffix, e.g. `step_merged`.
+fn run_output_path(cli: &Cli, suffix: &str) -> PathBuf {
+    let mut path = PathBuf::from(&cli.output);
+    if cli.output == "step" {
+        path.set_file_name(f!("{}_{suffix}", cli.output_name()));
     }
+    path
+}
 
-    // Create the file, fall back to a default if not
-    let f = File::create(p.with_extension(extension)).or_else(|e| {
-        warn!("{e}. Falling back to \"{default}\".",);
-        File::create(default).context("Unable to create fallback file")
-    })?;
+/// Output prefix for a single nuclide, e.g. `fisdef_2` becomes `fisdef_2_Co60`
+pub fn nuclide_output_path(path: &Path, nuclide: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(f!("{name}_{nuclide}"))
+}
 
-    Ok(f)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolation_modes() {
+        let linear = Interpolation::Linear.activity(100.0, 1.0, 0.5);
+        assert!((linear - 50.5).abs() < 1e-9);
+
+        let log_linear = Interpolation::LogLinear.activity(100.0, 1.0, 0.5);
+        assert!((log_linear - 10.0).abs() < 1e-9);
+
+        // the log of zero is undefined, so falls back to linear
+        let to_zero = Interpolation::LogLinear.activity(100.0, 0.0, 0.25);
+        assert!((to_zero - 75.0).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,137 @@
+//! Reader for manually defined sources
+//!
+//! Each line of the file is a single decay line of the form
+//! `nuclide activity_bq energy_kev intensity_pct`, for example:
+//!
+//! ```text
+//! # nuclide  activity [Bq]  energy [keV]  intensity [%]
+//! Co60       1.0e+09        1173.228      99.85
+//! Co60       1.0e+09        1332.492      99.9826
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored. The activity of a
+//! nuclide is taken from its first line. Only the energy and intensity of a
+//! line are known, so other decay data such as the decay mode are left unset
+//! and the nuclide needs no IAEA data of its own.
+//!
+//! Files with a `.csv` extension are instead read by column name, with the
+//! required `name`, `activity_bq`, `energy_kev`, and `intensity_pct` columns
+//! and optional `half_life_s` and `branching_ratio` columns:
+//!
+//! ```text
+//! name,activity_bq,energy_kev,intensity_pct,half_life_s
+//! Co60,1.0e+09,1173.228,99.85,1.663e+08
+//! Co60,1.0e+09,1332.492,99.9826,1.663e+08
+//! ```
+
+// internal
+use crate::source::{manual_record, Source};
+
+// neutronics toolbox
+use ntools::iaea;
+use ntools::utils::f;
+
+// standard lib
+use std::fs;
+use std::path::Path;
+
+// external
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+/// Read a file of manually defined decay lines into sources
+pub fn read(path: &Path) -> Result<Vec<Source>> {
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+    {
+        return read_csv(path);
+    }
+
+    let text =
+        fs::read_to_string(path).with_context(|| f!("Unable to read {:?}", path.display()))?;
+
+    let mut sources: Vec<Source> = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, activity, energy, intensity) =
+            parse_line(line).with_context(|| f!("Invalid entry on line {}: \"{line}\"", n + 1))?;
+
+        if let Some(source) = sources.iter_mut().find(|s| s.fispact_name == name) {
+            if source.fispact_activity != activity {
+                warn!("Ignoring different activity for {name} on line {}", n + 1);
+            }
+            source.iaea_records.push(manual_record(energy, intensity));
+            continue;
+        }
+
+        let Ok(nuclide) = iaea::Nuclide::try_from(name.clone()) else {
+            bail!(
+                "Could not convert \"{name}\" to a nuclide on line {}",
+                n + 1
+            )
+        };
+
+        let record = manual_record(energy, intensity);
+        sources.push(Source::from_manual(&name, activity, nuclide, vec![record]));
+    }
+
+    debug!("Found {} manually defined nuclides", sources.len());
+    Ok(sources)
+}
+
+/// Read a CSV file of manually defined decay lines into sources
+fn read_csv(path: &Path) -> Result<Vec<Source>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .with_context(|| f!("Unable to read {:?}", path.display()))?;
+    let headers = reader.headers()?.clone();
+
+    let mut sources: Vec<Source> = Vec::new();
+    for (n, row) in reader.records().enumerate() {
+        let row = row.with_context(|| f!("Invalid CSV row {}", n + 1))?;
+        let source = Source::from_csv_row(&row, &headers)
+            .with_context(|| f!("Invalid entry on row {}", n + 1))?;
+
+        match sources
+            .iter_mut()
+            .find(|s| s.fispact_name == source.fispact_name)
+        {
+            Some(existing) => {
+                if existing.fispact_activity != source.fispact_activity {
+                    warn!(
+                        "Ignoring different activity for {} on row {}",
+                        source.fispact_name,
+                        n + 1
+                    );
+                }
+                existing.iaea_records.extend(source.iaea_records);
+            }
+            None => sources.push(source),
+        }
+    }
+
+    debug!("Found {} manually defined nuclides", sources.len());
+    Ok(sources)
+}
+
+/// Split a line into the nuclide name, activity, energy, and intensity
+fn parse_line(line: &str) -> Result<(String, f64, f32, f32)> {
+    let tokens = line.split_whitespace().collect::<Vec<&str>>();
+    if tokens.len() != 4 {
+        bail!("expected 4 columns, found {}", tokens.len())
+    }
+
+    let activity = tokens[1].parse::<f64>().context("bad activity")?;
+    let energy = tokens[2].parse::<f32>().context("bad energy")?;
+    let intensity = tokens[3].parse::<f32>().context("bad intensity")?;
+
+    Ok((tokens[0].to_string(), activity, energy, intensity))
+}
@@ -1,8 +1,17 @@
 // internal
-use crate::create_file_with_fallback;
+use crate::beta;
+use crate::cli::{Cli, SdefParticle};
+use crate::fileio::{open_output, ExistsAction};
+use crate::mcnp_parser::ParsedSource;
+use crate::record_set::RecordSetExt;
 use crate::source::Source;
+use crate::spectrum::{aggregate_spectrum, binning};
+use crate::table::{ascii_safe, human_readable_halflife};
+use crate::wrappers::CliRadType;
 
 // neutronics toolbox
+use ntools::fispact::Inventory;
+use ntools::iaea::{self, IsomerState};
 use ntools::utils::{f, ValueExt};
 
 // standard lib
@@ -11,36 +20,1035 @@ use std::path::Path;
 
 // external
 use anyhow::Result;
+use itertools::{EitherOrBoth, Itertools};
+use log::{debug, trace, warn};
 
 const KEV_TO_MEV: f32 = 1.0e-03;
+const MAX_LINE_WIDTH: usize = 80;
+
+/// Largest relative difference of an SP total from its expected value
+const SP_TOTAL_TOLERANCE: f64 = 1e-5;
+const F15_BINS: usize = 20;
+const BETA_BINS: usize = 50;
+
+/// Target relative error for the suggested NPS
+const NPS_REL_ERROR: f64 = 0.05;
+
+/// Dose of a unit photon fluence [Sv cm2], roughly H*(10) at 1 MeV
+const NPS_DOSE_PER_FLUENCE: f64 = 5.0e-12;
+
+/// Depths at which weight window bounds are suggested, as fractions of the distance
+const WW_DEPTHS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+
+/// Photon attenuation coefficients [1/cm] of water against energy [MeV]
+const WATER_ATTENUATION: [(f32, f32); 25] = [
+    (0.01, 5.329),
+    (0.02, 0.8096),
+    (0.03, 0.3756),
+    (0.04, 0.2683),
+    (0.05, 0.2269),
+    (0.06, 0.2059),
+    (0.08, 0.1837),
+    (0.1, 0.1707),
+    (0.15, 0.1505),
+    (0.2, 0.1370),
+    (0.3, 0.1186),
+    (0.4, 0.1061),
+    (0.5, 0.09687),
+    (0.6, 0.08956),
+    (0.8, 0.07865),
+    (1.0, 0.07072),
+    (1.25, 0.06323),
+    (1.5, 0.05754),
+    (2.0, 0.04942),
+    (3.0, 0.03969),
+    (4.0, 0.03403),
+    (5.0, 0.03031),
+    (6.0, 0.02770),
+    (8.0, 0.02429),
+    (10.0, 0.02219),
+];
+
+/// Comment on the SC card of each nuclide unless '--mcnp-comment-format' is used
+pub const DEFAULT_COMMENT_FORMAT: &str =
+    "{name} decay data, t1/2 = {halflife}, norm = {norm} particles/decay";
+
+/// Default SC card comment when '--mcnp-comment-units' is used
+pub const DEFAULT_UNITS_COMMENT_FORMAT: &str =
+    "{name} decay data, t1/2 = {halflife}, activity = {activity}, norm = {norm}";
+
+/// Layout of the entries on SI and SP cards
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum McnpFormatMode {
+    /// As many entries as fit on each 80 character line
+    #[default]
+    Wrapped,
+    /// A single entry per continuation line, for readable diffs
+    PerLine,
+}
+
+/// Options for generating the MCNP cards
+#[derive(Debug, Clone)]
+pub struct McnpConfig {
+    /// Starting distribution number
+    pub id: usize,
+    /// Bin width [MeV] for histogram distributions rather than discrete lines
+    pub histogram: Option<f32>,
+    /// Particle designator for the SDEF card
+    pub particle: &'static str,
+    /// Original and target total activity [Bq] when activities are rescaled
+    pub scaling: Option<(f64, f64)>,
+    /// Weight activities by natural isotopic abundance
+    pub abundance_weight: bool,
+    /// Text written as-is before the source cards
+    pub header: Option<String>,
+    /// Text written as-is after the source cards
+    pub footer: Option<String>,
+    /// Path to the FISPACT file the sources came from
+    pub fispact_path: Option<String>,
+    /// Write the interval metadata comment block
+    pub context: bool,
+    /// Pad SI and SP entries so that their columns line up
+    pub pad_si_sp: bool,
+    /// Photon lines below this energy [MeV] are discarded
+    pub photon_cutoff: f32,
+    /// Template for the SC card comment of each nuclide
+    pub comment_format: String,
+    /// Significant figures of SI card line energies
+    pub energy_sig_figs: u8,
+    /// Merge every nuclide into a single energy distribution
+    pub consolidate: bool,
+    /// Replace any non-ASCII characters in comments and user text
+    pub ascii: bool,
+    /// Append a suggested F15 point detector tally
+    pub f15_tally: bool,
+    /// Distance [cm] for a suggested weight window block, if any
+    pub weight_window: Option<f32>,
+    /// Continuous Fermi spectra for beta sources rather than endpoint lines
+    pub continuous: bool,
+    /// Additional keywords for the SDEF card, e.g. "pos=0 0 0 wgt=2"
+    pub sdef_extra: Option<String>,
+    /// Dose per source particle [Sv] for a suggested NPS comment, if any
+    pub nps_dose: Option<f64>,
+    /// Link each nuclide to the IAEA chart of nuclides in a comment
+    pub iaea_url: bool,
+    /// Layout of the SI and SP card entries
+    pub format_mode: McnpFormatMode,
+    /// Annotate the quantities in comments with their units
+    pub comment_units: bool,
+    /// KSRC starting points [cm] to write in place of the SDEF card, if any
+    pub kcode: Option<Vec<[f64; 3]>>,
+    /// Title card to start the file with, where `{index}` is the interval
+    pub title: Option<String>,
+    /// Surface for a suggested surface source write card, if any
+    pub surface_source: Option<usize>,
+    /// Write each nuclide distribution to its own file read by the main file
+    pub split_files: bool,
+}
+
+impl From<&Cli> for McnpConfig {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            id: cli.id,
+            histogram: cli.mcnp_histogram,
+            particle: match cli.mcnp_sdef_par {
+                SdefParticle::Auto => sdef_particle(cli.rad),
+                SdefParticle::P => "p",
+                SdefParticle::E => "e",
+                SdefParticle::N => "n",
+            },
+            scaling: None,
+            abundance_weight: cli.mcnp_abundance_weight,
+            header: cli.mcnp_header.clone(),
+            footer: cli.mcnp_footer.clone(),
+            fispact_path: cli.path.clone(),
+            context: !cli.mcnp_no_header,
+            pad_si_sp: cli.pad_si_sp,
+            photon_cutoff: cli.mcnp_photon_cutoff,
+            comment_format: match (&cli.mcnp_comment_format, cli.mcnp_comment_units) {
+                (Some(template), _) => template.clone(),
+                (None, true) => DEFAULT_UNITS_COMMENT_FORMAT.to_string(),
+                (None, false) => DEFAULT_COMMENT_FORMAT.to_string(),
+            },
+            energy_sig_figs: cli.mcnp_round_energies,
+            consolidate: cli.consolidate_distributions || cli.mcnp_surface_source.is_some(),
+            ascii: cli.text_no_unicode,
+            f15_tally: cli.mcnp_f15_tally,
+            weight_window: cli.mcnp_weight_window,
+            continuous: cli.mcnp_continuous_energy
+                && matches!(cli.rad, CliRadType::BetaMinus | CliRadType::BetaPlus),
+            sdef_extra: cli.mcnp_sdef_extra.clone(),
+            nps_dose: cli.mcnp_nps,
+            iaea_url: cli.mcnp_comment_iaea_url,
+            format_mode: match cli.mcnp_si_sp_per_line {
+                true => McnpFormatMode::PerLine,
+                false => McnpFormatMode::Wrapped,
+            },
+            comment_units: cli.mcnp_comment_units,
+            kcode: cli.mcnp_kcode.then(|| match cli.mcnp_kcode_pos.is_empty() {
+                true => vec![[0.0; 3]],
+                false => cli
+                    .mcnp_kcode_pos
+                    .chunks_exact(3)
+                    .map(|p| [p[0], p[1], p[2]])
+                    .collect(),
+            }),
+            title: cli.mcnp_include_header.then(|| {
+                // a custom output name is more meaningful than the input file
+                let name = match cli.output.as_str() {
+                    "step" => cli
+                        .path
+                        .as_deref()
+                        .and_then(|p| Path::new(p).file_name())
+                        .map_or("stdin".to_string(), |n| n.to_string_lossy().to_string()),
+                    _ => cli.output_name().to_string(),
+                };
+                f!("fisdef: {name} interval {{index}} {} source", cli.rad)
+            }),
+            surface_source: cli.mcnp_surface_source,
+            split_files: cli.mcnp_split_files,
+        }
+    }
+}
+
+/// Infer the SDEF particle designator from the radiation type
+pub fn sdef_particle(radtype: CliRadType) -> &'static str {
+    match radtype {
+        CliRadType::Gamma | CliRadType::Xray => "p",
+        CliRadType::Electron | CliRadType::BetaMinus | CliRadType::BetaPlus => "e",
+        CliRadType::Alpha => "a",
+    }
+}
 
 /// Writes the mcnp cards to a file at the specified path.
-pub fn write(sources: &[Source], path: &Path, index: usize, id: usize) -> Result<()> {
-    let mut f = create_file_with_fallback(path, "i", &f!("step_{index}.i"))?;
-    let cards = generate_mcnp_cards(sources, id);
-    f.write_all(cards.as_bytes())?;
+///
+/// With `config.split_files` set, each nuclide distribution is written to its
+/// own `{name}_{nuclide}.i` file and read into the main file.
+///
+/// The interval is described in a comment block at the top of the file when
+/// an inventory is available.
+pub fn write(
+    sources: &[Source],
+    path: &Path,
+    inventory: Option<&Inventory>,
+    index: usize,
+    config: &McnpConfig,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let Some(mut f) = open_output(path, "i", &f!("step_{index}.i"), compress, exists)? else {
+        return Ok(());
+    };
+
+    if let Some(template) = &config.title {
+        f.write_all(title_card(template, index).as_bytes())?;
+    }
+
+    if let Some(inventory) = inventory.filter(|_| config.context) {
+        f.write_all(interval_context(inventory, index, config).as_bytes())?;
+    }
+
+    // a consolidated distribution is a single set of cards, so never split
+    match config.split_files && !config.consolidate {
+        true => write_split(sources, path, config, compress, exists, &mut f)?,
+        false => write_to_writer(sources, config, &mut f)?,
+    }
+    f.finish()
+}
+
+/// Title card and a description of when and how the file was generated
+///
+/// MCNP only reads the first 80 characters of the title, so anything beyond
+/// that is cut.
+fn title_card(template: &str, index: usize) -> String {
+    let mut title = template.replace("{index}", &index.to_string());
+    if title.chars().count() > MAX_LINE_WIDTH {
+        warn!("MCNP title is longer than {MAX_LINE_WIDTH} characters, truncating");
+        title = title.chars().take(MAX_LINE_WIDTH).collect();
+    }
+
+    f!(
+        "{title}\nc Generated by fisdef v{} on {}\n",
+        env!("CARGO_PKG_VERSION"),
+        utc_date()
+    )
+}
+
+/// Current date in UTC as YYYY-MM-DD
+///
+/// Converts days since the Unix epoch to a civil date, which avoids another
+/// dependency just for the title card.
+fn utc_date() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    let z = (seconds / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    f!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Comment block describing the interval the sources were generated from
+///
+/// The fisdef version is already on the title card when there is one, so is
+/// only included here otherwise.
+fn interval_context(inventory: &Inventory, index: usize, config: &McnpConfig) -> String {
+    let Some(interval) = inventory.intervals.get(index) else {
+        return String::new();
+    };
+
+    let version = match config.title {
+        Some(_) => None,
+        None => Some(f!("Generated by fisdef v{}", env!("CARGO_PKG_VERSION"))),
+    };
+
+    let lines = [
+        f!(
+            "FISPACT file     : {}",
+            config.fispact_path.as_deref().unwrap_or("stdin")
+        ),
+        f!("Interval index   : {index}"),
+        f!(
+            "Irradiation time : {}",
+            human_readable_time(interval.irradiation_time)
+        ),
+        f!(
+            "Cooling time     : {}",
+            human_readable_time(interval.cooling_time)
+        ),
+        f!("Total activity   : {} Bq", interval.activity.sci(5, 2)),
+        f!(
+            "Dose rate        : {} uSv/hr",
+            (interval.dose.rate * 1e6).sci(5, 2)
+        ),
+        f!("Photon cutoff    : {} MeV", config.photon_cutoff.sci(5, 2)),
+    ];
+
+    version
+        .iter()
+        .chain(&lines)
+        .map(|l| f!("c {l}\n"))
+        .collect::<String>()
+        + "c\n"
+}
+
+/// Human readable time in seconds, avoiding nonsense for zero durations
+fn human_readable_time(seconds: f64) -> String {
+    match seconds > 0.0 {
+        true => human_readable_halflife(Some(seconds as f32)),
+        false => "0 s".to_string(),
+    }
+}
+
+/// Writes the main distribution and a separate file for every nuclide
+fn write_split<W: Write>(
+    sources: &[Source],
+    path: &Path,
+    config: &McnpConfig,
+    compress: bool,
+    exists: ExistsAction,
+    mut writer: W,
+) -> Result<()> {
+    let sources = prepared_sources(sources, config);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("step");
+
+    // MCNP cannot read gzipped files, so anything read by the master is plain
+    if compress {
+        warn!("Nuclide files read by the MCNP master file are never compressed");
+    }
+
+    let mut card = activity_distribution(&sources, config);
+    for (i, s) in sources.iter().enumerate() {
+        let nuclide_path = path.with_file_name(f!("{name}_{}", s.fispact_name));
+        let f = open_output(
+            &nuclide_path,
+            "i",
+            &f!("{name}_{}.i", s.fispact_name),
+            false,
+            exists,
+        )?;
+
+        // skipped files are still read, as they already exist
+        if let Some(mut f) = f {
+            let cards = nuclide_cards(s, config.id + i + 1, config);
+            match config.ascii {
+                true => f.write_all(ascii_safe(&cards).as_bytes())?,
+                false => f.write_all(cards.as_bytes())?,
+            }
+            f.finish()?;
+        }
+
+        card += &f!("\nread file={name}_{}.i", s.fispact_name);
+    }
+
+    if config.f15_tally {
+        card += &f15_tally(&sources, config);
+    }
+
+    if let Some(distance) = config.weight_window {
+        card += &weight_window(&sources, distance, config);
+    }
+
+    if let Some(dose_per_nps) = config.nps_dose {
+        card += &nps_suggestion(dose_per_nps);
+    }
+
+    writer.write_all(with_header_footer(card, config).as_bytes())?;
+    Ok(())
+}
+
+/// Writes only the nuclide energy distributions to `{name}.erg.i`
+///
+/// Distributions are numbered from `config.id`, for users with their own SDEF
+/// card that only needs an `erg=d{id}` reference.
+pub fn write_erg(
+    sources: &[Source],
+    path: &Path,
+    index: usize,
+    config: &McnpConfig,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let Some(mut f) = open_output(path, "erg.i", &f!("step_{index}.erg.i"), compress, exists)?
+    else {
+        return Ok(());
+    };
+    f.write_all(energy_distributions(sources, config).as_bytes())?;
+    f.finish()
+}
+
+/// Writes the mcnp cards to any writer.
+pub fn write_to_writer<W: Write>(
+    sources: &[Source],
+    config: &McnpConfig,
+    mut writer: W,
+) -> Result<()> {
+    let cards = generate_mcnp_cards(sources, config);
+    writer.write_all(cards.as_bytes())?;
     Ok(())
 }
 
+/// Writes a binned spectrum as a single histogram distribution
+pub fn write_spectrum(
+    spectrum: &[(f32, f32)],
+    width: f32,
+    path: &Path,
+    index: usize,
+    id: usize,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("step");
+    let path = path.with_file_name(f!("{name}_spectrum"));
+
+    let Some(mut f) = open_output(&path, "i", &f!("step_{index}_spectrum.i"), compress, exists)?
+    else {
+        return Ok(());
+    };
+    let cards = spectrum_distribution(spectrum, width, id);
+    f.write_all(cards.as_bytes())?;
+    f.finish()
+}
+
+/// Make a histogram source distribution from binned spectrum data
+fn spectrum_distribution(spectrum: &[(f32, f32)], width: f32, id: usize) -> String {
+    let comment = f!(
+        "sc{id:<5} Binned spectrum over all nuclides ({} bins)",
+        spectrum.len()
+    );
+
+    // histogram bins need the lower edge of the first bin
+    let mut edges = spectrum
+        .iter()
+        .map(|(centre, _)| centre - 0.5 * width)
+        .collect::<Vec<f32>>();
+    if let Some((centre, _)) = spectrum.last() {
+        edges.push(centre + 0.5 * width);
+    }
+
+    let si_card = f!(
+        "si{id} H {}",
+        edges
+            .iter()
+            .map(|e| (e * KEV_TO_MEV).sci(5, 2))
+            .collect::<Vec<String>>()
+            .join(" ")
+    );
+
+    let sp_card = f!(
+        "sp{id} D 0 {}",
+        spectrum
+            .iter()
+            .map(|(_, intensity)| intensity.sci(5, 2))
+            .collect::<Vec<String>>()
+            .join(" ")
+    );
+
+    f!(
+        "{}\n{}\n{}\nc",
+        comment,
+        &wrap_text(si_card, MAX_LINE_WIDTH, "        "),
+        &wrap_text(sp_card, MAX_LINE_WIDTH, "        ")
+    )
+}
+
+/// Incrementally collects sources for a set of MCNP source cards
+#[derive(Debug, Clone)]
+pub struct McnpOutput {
+    config: McnpConfig,
+    sources: Vec<Source>,
+}
+
+impl McnpOutput {
+    /// Start an empty set of cards with the given options
+    pub fn new(config: McnpConfig) -> Self {
+        Self {
+            config,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Add a nuclide to the source
+    pub fn push_source(&mut self, source: &Source) {
+        self.sources.push(source.clone());
+    }
+
+    /// Generate the full set of cards for every source added so far
+    pub fn cards(&self) -> String {
+        generate_mcnp_cards(&self.sources, &self.config)
+    }
+}
+
+/// Activity-weighted distribution over nuclides, for use in custom decks
+pub fn generate_activity_distribution(sources: &[Source], config: &McnpConfig) -> String {
+    activity_distribution(sources, config)
+}
+
+/// Decay data distribution for a single nuclide, for use in custom decks
+pub fn generate_nuclide_distribution(source: &Source, id: usize, config: &McnpConfig) -> String {
+    nuclide_distribution(source, id, config)
+}
+
 /// Make source distribution cards for every nuclide
-fn generate_mcnp_cards(sources: &[Source], id: usize) -> String {
-    let mut card = activity_distribution(sources, id);
-    for (i, s) in sources.iter().enumerate() {
-        card += &nuclide_distribution(s, id + i + 1);
+pub fn generate_mcnp_cards(sources: &[Source], config: &McnpConfig) -> String {
+    let sources = prepared_sources(sources, config);
+
+    let mut card = match config.consolidate {
+        true => {
+            let sdef = sdef_card(config.particle, config.id, config);
+            f!(
+                "{sdef}\n{}",
+                consolidated_distribution(&sources, config.id, config)
+            )
+        }
+        false => {
+            let mut card = activity_distribution(&sources, config);
+            for (i, s) in sources.iter().enumerate() {
+                card += &nuclide_cards(s, config.id + i + 1, config);
+            }
+            card
+        }
+    };
+
+    if config.f15_tally {
+        card += &f15_tally(&sources, config);
+    }
+
+    if let Some(distance) = config.weight_window {
+        card += &weight_window(&sources, distance, config);
+    }
+
+    if let Some(dose_per_nps) = config.nps_dose {
+        card += &nps_suggestion(dose_per_nps);
+    }
+
+    if let Some(surface) = config.surface_source {
+        card += &surface_source(surface, config);
+    }
+
+    with_header_footer(card, config)
+}
+
+/// Suggested point detector tally binned over the source energy range
+///
+/// Point detectors only exist for neutrons and photons, so nothing is written
+/// for any other particle.
+fn f15_tally(sources: &[Source], config: &McnpConfig) -> String {
+    if !matches!(config.particle, "n" | "p") {
+        warn!(
+            "Skipping the F15 tally, point detectors do not accept particle \"{}\"",
+            config.particle
+        );
+        return String::new();
+    }
+
+    let comment = [
+        "c",
+        "c Suggested point detector tally generated by fisdef",
+        "c The detector position (x y z) and exclusion radius are placeholders",
+        "c and must be adjusted to suit the problem geometry.",
+        "c",
+        "c The detector is 100 cm along the z axis (x y z = 0 0 100), with no",
+        "c exclusion radius (R0 = 0).",
+        "c",
+    ]
+    .join("\n");
+
+    let tally = f!("f15:{} 0 0 100 0", config.particle);
+
+    // same uniform binning as the binned spectrum output
+    let energies = match binning(sources, F15_BINS) {
+        Some((lower, width)) => (1..=F15_BINS)
+            .map(|i| ((lower + i as f32 * width) * KEV_TO_MEV).sci(5, 2))
+            .collect::<Vec<String>>(),
+        None => Vec::new(),
+    };
+
+    let mut cards = f!("\n{comment}\n{tally}");
+    if !energies.is_empty() {
+        let e_card = f!("e15     {}", energies.join(" "));
+        cards += &f!("\n{}", wrap_text(e_card, MAX_LINE_WIDTH, "        "));
+    }
+    cards + "\nc"
+}
+
+/// Surface source write card for a later surface source read run
+///
+/// The consolidated distribution is the source of the writing run, and the
+/// particles crossing the surface are recorded for reuse.
+fn surface_source(surface: usize, config: &McnpConfig) -> String {
+    let read_back = f!("c RSSA and read it back with \"ssr old={surface}\" in the next run.");
+    let comment = [
+        "c",
+        "c Suggested surface source write generated by fisdef",
+        "c Particles crossing the surface are written to WSSA. Rename this to",
+        read_back.as_str(),
+        "c",
+    ]
+    .join("\n");
+
+    f!("\n{comment}\nssw     {surface} pty={}\nc", config.particle)
+}
+
+/// Commented-out weight window bounds from attenuation of the dominant line
+///
+/// Bounds follow `exp(-mu*r)` at fractions of the distance, with `mu` taken
+/// for water at the energy of the strongest line. Only photons are supported.
+fn weight_window(sources: &[Source], distance_cm: f32, config: &McnpConfig) -> String {
+    if config.particle != "p" {
+        debug!("Weight window suggestion is only available for photons");
+        return String::new();
+    }
+
+    let Some((energy, _)) = aggregate_spectrum(sources)
+        .into_iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+    else {
+        return String::new();
+    };
+
+    let energy_mev = energy * KEV_TO_MEV;
+    let mu = water_attenuation(energy_mev);
+    let e_max = sources
+        .iter()
+        .flat_map(|s| s.iaea_records.iter().filter_map(|r| r.energy))
+        .fold(0.0_f32, f32::max)
+        * KEV_TO_MEV;
+
+    let mut lines = vec![
+        "c".to_string(),
+        "c Rough weight window starting point generated by fisdef".to_string(),
+        "c This is only a first guess and should be tuned with the MCNP weight".to_string(),
+        "c window generator. Lower bounds are exp(-mu*r) for water, one per cell".to_string(),
+        "c in order of increasing depth.".to_string(),
+        f!(
+            "c   mu = {} /cm at the dominant {} MeV line",
+            mu.sci(3, 2),
+            energy_mev.sci(3, 2)
+        ),
+    ];
+
+    let mut bounds = Vec::new();
+    for fraction in WW_DEPTHS {
+        let r = fraction * distance_cm;
+        let bound = (-mu * r).exp();
+        lines.push(f!("c   r = {r:.1} cm : {}", bound.sci(3, 2)));
+        bounds.push(bound.sci(3, 2));
+    }
+
+    lines.push(f!("c wwe:p {}", (e_max * 1.01).sci(3, 2)));
+    lines.push(f!("c wwn1:p 1.0 {}", bounds.join(" ")));
+    f!("\n{}\nc", lines.join("\n"))
+}
+
+/// Order of magnitude NPS for the dose per source particle of a detector
+///
+/// Each history reaching a 1 cm2 detector scores roughly the dose of a unit
+/// fluence `h`, so a dose `d` per source particle means that a fraction `d/h`
+/// of histories score. An analogue estimate then needs `h / (R^2 d)` histories
+/// for a relative error `R`.
+fn nps_suggestion(dose_per_nps_sv: f64) -> String {
+    if dose_per_nps_sv <= 0.0 {
+        warn!("Unable to suggest an NPS without a positive dose per source particle");
+        return String::new();
+    }
+
+    let nps = (NPS_DOSE_PER_FLUENCE / (NPS_REL_ERROR * NPS_REL_ERROR * dose_per_nps_sv)).ceil();
+
+    [
+        "c".to_string(),
+        f!("c Suggested: nps {}", nps.sci(1, 2)),
+        f!(
+            "c   for {}% error on a 1 cm2 detector, order of magnitude only",
+            NPS_REL_ERROR * 100.0
+        ),
+        "c".to_string(),
+    ]
+    .iter()
+    .fold(String::new(), |acc, line| acc + "\n" + line)
+}
+
+/// Log-log interpolation of the water attenuation coefficient [1/cm]
+fn water_attenuation(energy_mev: f32) -> f32 {
+    let n = WATER_ATTENUATION.len();
+    let (first, last) = (WATER_ATTENUATION[0], WATER_ATTENUATION[n - 1]);
+    if energy_mev <= first.0 {
+        return first.1;
+    }
+    if energy_mev >= last.0 {
+        return last.1;
+    }
+
+    let upper = WATER_ATTENUATION
+        .iter()
+        .position(|(e, _)| *e >= energy_mev)
+        .unwrap_or(n - 1);
+    let (e0, mu0) = WATER_ATTENUATION[upper - 1];
+    let (e1, mu1) = WATER_ATTENUATION[upper];
+    let fraction = (energy_mev / e0).ln() / (e1 / e0).ln();
+    (mu0.ln() + fraction * (mu1 / mu0).ln()).exp()
+}
+
+/// Single distribution of every line from every nuclide
+///
+/// Each unique energy is weighted by the emission rate summed over nuclides,
+/// i.e. `activity * intensity / 100`, relative to the total source strength.
+pub fn consolidated_distribution(sources: &[Source], id: usize, config: &McnpConfig) -> String {
+    let lines = aggregate_spectrum(sources);
+    let total = lines.iter().map(|(_, rate)| rate).sum::<f64>();
+
+    let comment = f!(
+        "sc{id:<5} Consolidated distribution of {} nuclides ({} particles/s)",
+        sources.len(),
+        total.sci(5, 2)
+    );
+
+    let energies = lines
+        .iter()
+        .map(|(energy, _)| format_mev(energy * KEV_TO_MEV, config.energy_sig_figs))
+        .collect::<Vec<String>>();
+
+    let probabilities = lines
+        .iter()
+        .map(|(_, rate)| match total > 0.0 {
+            true => (rate / total).sci(5, 2),
+            false => 0.0_f64.sci(5, 2),
+        })
+        .collect::<Vec<String>>();
+
+    let (si_card, sp_card) = si_sp_cards(
+        f!("si{id} L "),
+        f!("sp{id:<6}"),
+        &energies,
+        &probabilities,
+        config,
+    );
+
+    f!(
+        "{}\n{}\n{}\nc",
+        comment,
+        &format_card(si_card, config.format_mode),
+        &format_card(sp_card, config.format_mode)
+    )
+}
+
+/// Energy distributions for every nuclide without the SDEF or activity cards
+pub fn energy_distributions(sources: &[Source], config: &McnpConfig) -> String {
+    let sources = prepared_sources(sources, config);
+
+    let card = sources
+        .iter()
+        .enumerate()
+        .map(|(i, s)| nuclide_cards(s, config.id + i, config))
+        .collect::<String>();
+
+    with_header_footer(card.trim_start().to_string(), config)
+}
+
+/// Apply any abundance weighting and energy cutoff requested in the config
+fn prepared_sources(sources: &[Source], config: &McnpConfig) -> Vec<Source> {
+    let sources = match config.abundance_weight {
+        true => abundance_weighted(sources),
+        false => sources.to_vec(),
+    };
+
+    match config.particle {
+        "p" => above_cutoff(sources, config.photon_cutoff),
+        _ => sources,
+    }
+}
+
+/// Remove lines below the energy cutoff, and any nuclides left without lines
+fn above_cutoff(mut sources: Vec<Source>, cutoff_mev: f32) -> Vec<Source> {
+    let mut removed = 0;
+    for s in sources.iter_mut() {
+        let n = s.iaea_records.len();
+        s.iaea_records
+            .retain(|r| r.energy.is_some_and(|e| e * KEV_TO_MEV >= cutoff_mev));
+        removed += n - s.iaea_records.len();
+    }
+
+    debug!(
+        "Removed {removed} records below the {} MeV cutoff",
+        cutoff_mev.sci(5, 2)
+    );
+    sources.retain(|s| !s.iaea_records.is_empty());
+    sources
+}
+
+/// Discrete or histogram distribution for a single nuclide
+fn nuclide_cards(source: &Source, id: usize, config: &McnpConfig) -> String {
+    // every distribution starts on a new line, so the link can go first
+    let link = match config.iaea_url {
+        true => f!("\nc IAEA data: {}", iaea_chart_url(source)),
+        false => String::new(),
+    };
+
+    if config.continuous {
+        return link + &continuous_beta_cards(source, id, config);
+    }
+
+    link + &match config.histogram {
+        Some(width) if width > 0.0 => to_histogram_cards(source, width, id, config),
+        _ => nuclide_distribution(source, id, config),
     }
-    card
+}
+
+/// Link to the IAEA chart of nuclides for a nuclide, e.g. `nucleus=60Co`
+fn iaea_chart_url(source: &Source) -> String {
+    let name = source.iaea_nuclide.name();
+    let split = name
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(name.len());
+    let (symbol, mass) = name.split_at(split);
+    f!("https://www-nds.iaea.org/relnsd/vcharthtml/VChartHTML.html?nucleus={mass}{symbol}")
+}
+
+/// Wrap the cards in any user provided header and footer text
+///
+/// Everything is made ASCII-safe here if requested, since this is the last
+/// step for every set of cards.
+fn with_header_footer(mut card: String, config: &McnpConfig) -> String {
+    if let Some(header) = &config.header {
+        card = f!("{}\n{card}", header.trim_end_matches('\n'));
+    }
+
+    if let Some(footer) = &config.footer {
+        card = f!("{card}\n{}", footer.trim_end_matches('\n'));
+    }
+
+    match config.ascii {
+        true => ascii_safe(&card),
+        false => card,
+    }
+}
+
+/// Combine several parsed sources into one, renumbered from `config.id`
+///
+/// The master weights are absolute (activity * particles/decay), so the new
+/// master distribution simply samples every sub-source by its original weight.
+pub fn merged_cards(sources: &[ParsedSource], config: &McnpConfig) -> String {
+    let id = config.id;
+    let particle = sources
+        .iter()
+        .find_map(|s| s.particle.as_deref())
+        .unwrap_or(config.particle);
+
+    let n = sources.iter().map(|s| s.distributions.len()).sum::<usize>();
+    let total = sources.iter().map(|s| s.strength()).sum::<f64>();
+
+    let sdef = sdef_card(particle, id, config);
+    let comment = f!(
+        "sc{id:<5} Merged source distribution of {} files ({n} distributions, {} total)",
+        sources.len(),
+        total.sci(5, 2)
+    );
+
+    let mut si_card = f!("si{:<6}", f!("{id} S "));
+    let mut sp_card = f!("sp{id:<6}");
+    let mut distributions = String::new();
+
+    let all = sources
+        .iter()
+        .flat_map(|s| s.master.iter().zip(&s.distributions));
+    for (i, ((_, weight), d)) in all.enumerate() {
+        let new_id = id + i + 1;
+        trace!("Distribution {} renumbered to {new_id}", d.id);
+        si_card += &f!("{new_id} ");
+        sp_card += &f!("{} ", weight.sci(5, 2));
+
+        let option = |o: &Option<String>| o.as_ref().map_or(String::new(), |o| f!("{o} "));
+        let si = f!("si{new_id} {}{}", option(&d.si_option), d.si.join(" "));
+        let sp = f!("sp{new_id} {}{}", option(&d.sp_option), d.sp.join(" "));
+
+        distributions += &f!(
+            "\nsc{new_id:<5} {}\n{}\n{}\nc",
+            d.comment.as_deref().unwrap_or("merged distribution"),
+            format_card(si, config.format_mode),
+            format_card(sp, config.format_mode)
+        );
+    }
+
+    let card = f!(
+        "{}\n{}\n{}\n{}\nc{distributions}",
+        sdef,
+        comment,
+        &format_card(si_card, config.format_mode),
+        &format_card(sp_card, config.format_mode)
+    );
+
+    with_header_footer(card, config)
+}
+
+/// Scale every activity by the natural abundance of the nuclide
+fn abundance_weighted(sources: &[Source]) -> Vec<Source> {
+    sources
+        .iter()
+        .cloned()
+        .map(|mut s| {
+            s.fispact_activity *= natural_abundance(&s.iaea_nuclide).unwrap_or_else(|| {
+                debug!("Assuming abundance of 1.0 for {}", s.fispact_name);
+                1.0
+            });
+            s
+        })
+        .collect()
+}
+
+/// Natural isotopic abundance fraction of common primordial radionuclides
+///
+/// Activation products do not occur naturally, so anything not listed here
+/// is left to the caller to assume a fraction of 1.0.
+fn natural_abundance(nuclide: &iaea::Nuclide) -> Option<f64> {
+    if matches!(nuclide.state, IsomerState::Excited(_)) {
+        return None;
+    }
+
+    let abundance = match nuclide.name().as_str() {
+        "K40" => 1.17e-4,
+        "V50" => 2.50e-3,
+        "Rb87" => 0.2783,
+        "In115" => 0.9571,
+        "Te128" => 0.3174,
+        "Te130" => 0.3408,
+        "La138" => 8.88e-4,
+        "Nd144" => 0.2380,
+        "Sm147" => 0.1499,
+        "Sm148" => 0.1124,
+        "Lu176" => 0.02599,
+        "Re187" => 0.6260,
+        "Th232" => 1.0,
+        "U234" => 5.4e-5,
+        "U235" => 7.204e-3,
+        "U238" => 0.992742,
+        _ => return None,
+    };
+
+    Some(abundance)
+}
+
+/// SDEF card sampling energies from distribution `id`
+///
+/// Any extra keywords are appended, and a `par=` among them replaces the
+/// inferred particle.
+fn sdef_card(particle: &str, id: usize, config: &McnpConfig) -> String {
+    if let Some(positions) = &config.kcode {
+        return ksrc_card(positions);
+    }
+
+    let Some(extra) = config.sdef_extra.as_deref().map(str::trim) else {
+        return f!("sdef    par={particle} erg=d{id}");
+    };
+
+    let card = match extra.to_lowercase().contains("par=") {
+        true => {
+            warn!("Using the particle from --mcnp-sdef-extra rather than par={particle}");
+            f!("sdef    erg=d{id} {extra}")
+        }
+        false => f!("sdef    par={particle} erg=d{id} {extra}"),
+    };
+
+    wrap_text(card, MAX_LINE_WIDTH, "        ")
+}
+
+/// KSRC card at every starting point, replacing the SDEF card
+///
+/// The distributions that follow are left for a secondary photon source.
+fn ksrc_card(positions: &[[f64; 3]]) -> String {
+    let points = positions
+        .iter()
+        .map(|[x, y, z]| f!("{x} {y} {z}"))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    [
+        "c WARNING: approximate criticality source, with no geometry information".to_string(),
+        "c Distributions below are only for a secondary photon source".to_string(),
+        "c kcode   10000 1.0 50 250    $ suggested, adjust as needed".to_string(),
+        wrap_text(f!("ksrc    {points}"), MAX_LINE_WIDTH, "        "),
+    ]
+    .join("\n")
 }
 
 /// Generates a formatted comment string for the main source distribution.
 ///
 /// The comment includes the source ID and the total normalized source count
-/// per particle.
-fn activity_distribution(sources: &[Source], id: usize) -> String {
-    let comment = f!(
+/// per particle. The SDEF card sampling from this distribution is included.
+fn activity_distribution(sources: &[Source], config: &McnpConfig) -> String {
+    let id = config.id;
+    let sdef = sdef_card(config.particle, id, config);
+
+    // particles per decay of the mixture
+    let total_activity = sources.iter().map(|s| s.fispact_activity).sum::<f64>();
+    let norm = total_photon_yield_per_second(sources) / total_activity;
+
+    let mut comment = f!(
         "sc{id:<5} Main source distribution ({} counts/src particle)",
-        total_norm(sources).sci(5, 2)
+        norm.sci(5, 2)
     );
 
+    if let Some((original, target)) = config.scaling {
+        comment += &f!(" (scaled from {original:.3e} to {target:.3e} Bq)");
+    }
+
+    if config.comment_units {
+        comment += ", weights in Bq\u{00b7}particles/decay";
+    }
+
     let mut si_card = f!("si{:<6}", f!("{id} S "));
     let mut sp_card = f!("sp{id:<6}");
 
@@ -49,7 +1057,7 @@ fn activity_distribution(sources: &[Source], id: usize) -> String {
         si_card += &f!("{dist_id} ");
         sp_card += &f!(
             "{}    $ {:<6} = {} Bq * {} particles/decay ",
-            (s.fispact_activity * s.norm()).sci(5, 2),
+            s.photon_yield_per_second().sci(5, 2),
             s.fispact_name,
             s.fispact_activity.sci(5, 2),
             s.norm().sci(5, 2),
@@ -57,64 +1065,466 @@ fn activity_distribution(sources: &[Source], id: usize) -> String {
     }
 
     f!(
-        "{}\n{}\n{}\nc",
+        "{}\n{}\n{}\n{}\nc",
+        sdef,
         comment,
-        &wrap_text(si_card, 80, "        "),
-        &wrap_text(sp_card, 80, "        ")
+        &format_card(si_card, config.format_mode),
+        &format_card(sp_card, config.format_mode)
     )
 }
 
-// Find the total normalisation factor
-fn total_norm(sources: &[Source]) -> f64 {
-    let total_activity = sources.iter().map(|s| s.fispact_activity).sum::<f64>();
+/// Particles emitted per second by every nuclide together
+fn total_photon_yield_per_second(sources: &[Source]) -> f64 {
+    sources.iter().map(|s| s.photon_yield_per_second()).sum()
+}
 
-    sources
-        .iter()
-        .map(|s| (s.fispact_activity / total_activity) * s.norm())
-        .sum::<f64>()
+/// Energy [MeV] in scientific notation with the given significant figures
+///
+/// e.g. 1173.228 keV is written as 1.173e+00 with 4 significant figures.
+fn format_mev(energy_mev: f32, sig_figs: u8) -> String {
+    energy_mev.sci(sig_figs.saturating_sub(1) as usize, 2)
+}
+
+/// Fill in the placeholders of a comment template for a nuclide
+///
+/// Supports `{id}`, `{name}`, `{iaea_name}`, `{activity}`, `{norm}`,
+/// `{n_lines}`, `{dominant_energy_kev}`, and `{halflife}`. With `units` set,
+/// the activity and norm include their units of Bq and particles/decay.
+/// Isomer names are followed by their excitation energy.
+fn format_comment(template: &str, source: &Source, id: usize, units: bool) -> String {
+    let dominant_energy = source
+        .iaea_records
+        .dominant()
+        .and_then(|r| r.energy)
+        .map_or("-".to_string(), |e| f!("{e:.3}"));
+
+    let (activity_unit, norm_unit) = match units {
+        true => (" Bq", " particles/decay"),
+        false => ("", ""),
+    };
+
+    let replacements = [
+        ("{id}", id.to_string()),
+        ("{name}", source.name_with_isomer_energy()),
+        ("{iaea_name}", source.iaea_nuclide.name_with_state()),
+        (
+            "{activity}",
+            f!("{}{activity_unit}", source.fispact_activity.sci(5, 2)),
+        ),
+        // this is already ignoring None intensities
+        ("{norm}", f!("{}{norm_unit}", source.norm().sci(5, 2))),
+        ("{n_lines}", source.iaea_records.len().to_string()),
+        ("{dominant_energy_kev}", dominant_energy),
+        ("{halflife}", human_readable_halflife(source.half_life())),
+    ];
+
+    let mut comment = template.to_string();
+    for (placeholder, value) in replacements {
+        comment = comment.replace(placeholder, &value);
+    }
+
+    if source.from_chain {
+        comment += " (computed from chain)";
+    }
+
+    f!("sc{id:<5} {comment}")
 }
 
 /// Make a single source distribution for a nuclide
-fn nuclide_distribution(source: &Source, id: usize) -> String {
-    // Create a comment line with nuclide name and normalization factor
+fn nuclide_distribution(source: &Source, id: usize, config: &McnpConfig) -> String {
+    // Create a comment line, by default the nuclide name and normalization
+    let comment = format_comment(&config.comment_format, source, id, config.comment_units);
+
+    let energies = source
+        .iaea_records
+        .iter()
+        .map(|record| format_mev(record.energy.unwrap() * KEV_TO_MEV, config.energy_sig_figs))
+        .collect::<Vec<String>>();
+
+    let probabilities = source
+        .iaea_records
+        .iter()
+        .map(|record| (record.intensity.unwrap() * 1e-2).sci(5, 2))
+        .collect::<Vec<String>>();
+
+    // Create the SI and SP cards, optionally lined up column by column
+    let (si_card, sp_card) = si_sp_cards(
+        f!("si{id} L "),
+        f!("sp{id:<6}"),
+        &energies,
+        &probabilities,
+        config,
+    );
+
+    // Combine the comment, SI card, and SP card with proper formatting
+    f!(
+        "\n{}\n{}\n{}\nc",
+        comment,
+        &format_card(si_card, config.format_mode),
+        &format_card(sp_card, config.format_mode)
+    )
+}
+
+/// Make a single histogram source distribution for a nuclide
+///
+/// Bin edges run from `E_min - w/2` to `E_max + w/2` in steps of the bin width
+/// `w`, so any lines closer than `w` are merged into the same bin. Falls back
+/// to discrete lines if there is nothing to bin.
+pub fn to_histogram_cards(
+    source: &Source,
+    bin_width_mev: f32,
+    id: usize,
+    config: &McnpConfig,
+) -> String {
+    let lines = source
+        .iaea_records
+        .iter()
+        .map(|record| {
+            (
+                record.energy.unwrap() * KEV_TO_MEV,
+                record.intensity.unwrap() * 1e-2,
+            )
+        })
+        .collect::<Vec<(f32, f32)>>();
+
+    let Some((edges, weights)) = histogram(&lines, bin_width_mev) else {
+        warn!(
+            "Unable to bin {} lines of {} into {} MeV bins, using discrete lines",
+            lines.len(),
+            source.fispact_name,
+            bin_width_mev
+        );
+        return nuclide_distribution(source, id, config);
+    };
+
     let comment = f!(
-        "sc{id:<5} {} decay data, norm = {} particles/decay",
-        source.fispact_name,
-        source.norm().sci(5, 2) // this is already ignoring None intensities
+        "{}, bin width = {} MeV",
+        format_comment(&config.comment_format, source, id, config.comment_units),
+        bin_width_mev.sci(5, 2)
     );
 
-    // Create the SI card with energy values
-    let si_card = f!(
-        "si{id} L {}",
-        source
-            .iaea_records
-            .iter()
-            .map(|record| (record.energy.unwrap() * KEV_TO_MEV).sci(5, 2))
-            .collect::<Vec<String>>()
-            .join(" ")
+    let edges = edges
+        .iter()
+        .map(|e| format_mev(*e, config.energy_sig_figs))
+        .collect::<Vec<String>>();
+
+    // histogram probabilities start with an empty bin below the first edge
+    let probabilities = std::iter::once("0".to_string())
+        .chain(weights.iter().map(|w| w.sci(5, 2)))
+        .collect::<Vec<String>>();
+
+    let (si_card, sp_card) = si_sp_cards(
+        f!("si{id} H "),
+        f!("sp{id} D "),
+        &edges,
+        &probabilities,
+        config,
     );
 
-    // Create the SP card with intensity values
-    let sp_card = f!(
-        "sp{id:<6}{}",
-        source
-            .iaea_records
-            .iter()
-            .map(|record| (record.intensity.unwrap() * 1e-2).sci(5, 2))
-            .collect::<Vec<String>>()
-            .join(" ")
+    f!(
+        "\n{}\n{}\n{}\nc",
+        comment,
+        &format_card(si_card, config.format_mode),
+        &format_card(sp_card, config.format_mode)
+    )
+}
+
+/// Bin edges and integrated weights of (energy, weight) lines
+///
+/// A single line, or lines all at the same energy, fill a single bin centred
+/// on that energy. The first edge is clamped to zero for negative energies.
+fn histogram(lines: &[(f32, f32)], bin_width: f32) -> Option<(Vec<f32>, Vec<f32>)> {
+    if lines.is_empty() || bin_width <= 0.0 {
+        return None;
+    }
+
+    let e_min = lines.iter().map(|l| l.0).fold(f32::INFINITY, f32::min);
+    let e_max = lines.iter().map(|l| l.0).fold(f32::NEG_INFINITY, f32::max);
+
+    let lower = e_min - 0.5 * bin_width;
+    let n_bins = match e_max > e_min {
+        true => ((e_max - e_min) / bin_width).ceil() as usize + 1,
+        false => 1,
+    };
+
+    // integrate the weight of every line falling in each bin
+    let mut weights = vec![0.0_f32; n_bins];
+    for (energy, weight) in lines {
+        let bin = (((energy - lower) / bin_width) as usize).min(n_bins - 1);
+        weights[bin] += weight;
+    }
+
+    let edges = (0..=n_bins)
+        .map(|i| (lower + i as f32 * bin_width).max(0.0))
+        .collect::<Vec<f32>>();
+
+    Some((edges, weights))
+}
+
+/// Make a histogram of the Fermi spectrum of every beta branch of a nuclide
+///
+/// Record energies are taken as endpoints, and the histogram runs from zero
+/// to the highest of them in uniform bins.
+fn continuous_beta_cards(source: &Source, id: usize, config: &McnpConfig) -> String {
+    let spectrum = beta::nuclide_spectrum(source, BETA_BINS);
+
+    let comment = f!(
+        "{}, Fermi spectrum",
+        format_comment(&config.comment_format, source, id, config.comment_units)
+    );
+
+    let edges = std::iter::once("0".to_string())
+        .chain(
+            spectrum
+                .iter()
+                .map(|(e, _)| format_mev(*e, config.energy_sig_figs)),
+        )
+        .collect::<Vec<String>>();
+
+    let probabilities = std::iter::once("0".to_string())
+        .chain(spectrum.iter().map(|(_, w)| w.sci(5, 2)))
+        .collect::<Vec<String>>();
+
+    let (si_card, sp_card) = si_sp_cards(
+        f!("si{id} H "),
+        f!("sp{id} D "),
+        &edges,
+        &probabilities,
+        config,
     );
 
-    // Combine the comment, SI card, and SP card with proper formatting
     f!(
         "\n{}\n{}\n{}\nc",
         comment,
-        &wrap_text(si_card, 80, "        "),
-        &wrap_text(sp_card, 80, "        ")
+        &format_card(si_card, config.format_mode),
+        &format_card(sp_card, config.format_mode)
+    )
+}
+
+/// SI and SP cards from their entries, lined up column by column if asked
+///
+/// The `si` and `sp` prefixes are the card names and options, e.g. "si1 L ",
+/// as written before the entries when they are not lined up.
+fn si_sp_cards(
+    si: String,
+    sp: String,
+    energies: &[String],
+    probabilities: &[String],
+    config: &McnpConfig,
+) -> (String, String) {
+    match config.pad_si_sp {
+        true => {
+            let (si_entries, sp_entries) = aligned_si_sp(energies, probabilities);
+            (
+                f!("{:<8}{si_entries}", si.trim_end()),
+                f!("{:<8}{sp_entries}", sp.trim_end()),
+            )
+        }
+        false => (
+            f!("{si}{}", energies.join(" ")),
+            f!("{sp}{}", probabilities.join(" ")),
+        ),
+    }
+}
+
+/// Pad each SI and SP entry to a common width so that columns line up
+fn aligned_si_sp(energies: &[String], probabilities: &[String]) -> (String, String) {
+    let (si, sp): (Vec<String>, Vec<String>) = energies
+        .iter()
+        .zip_longest(probabilities)
+        .map(|pair| {
+            let (e, p) = match pair {
+                EitherOrBoth::Both(e, p) => (e.as_str(), p.as_str()),
+                EitherOrBoth::Left(e) => (e.as_str(), ""),
+                EitherOrBoth::Right(p) => ("", p.as_str()),
+            };
+            let width = e.len().max(p.len());
+            (f!("{e:<width$}"), f!("{p:<width$}"))
+        })
+        .unzip();
+
+    (
+        si.join(" ").trim_end().to_string(),
+        sp.join(" ").trim_end().to_string(),
     )
 }
 
-// wrap everything to a fixed number of characters for mcnp
+/// Basic checks on generated cards, returning a description of any problems
+///
+/// Probabilities are not required to sum to 1.0 because MCNP normalises every
+/// SP card, so only negative or all-zero probabilities are reported.
+pub fn validate_mcnp_cards(cards: &str, config: &McnpConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    for (i, line) in cards.lines().enumerate() {
+        if line.len() > MAX_LINE_WIDTH {
+            warnings.push(f!(
+                "Line {} exceeds {MAX_LINE_WIDTH} characters ({})",
+                i + 1,
+                line.len()
+            ));
+        }
+    }
+
+    if config.kcode.is_none() && !cards.contains(&f!("erg=d{}", config.id)) {
+        warnings.push(f!("No SDEF card samples from distribution {}", config.id));
+    }
+
+    let (si_cards, sp_cards) = distribution_cards(cards);
+
+    for (n, (id, option, entries)) in si_cards.iter().enumerate() {
+        if si_cards[..n].iter().any(|(other, _, _)| other == id) {
+            warnings.push(f!("Distribution {id} is defined more than once"));
+        }
+
+        if option != "s" && entries.iter().any(|e| *e < 0.0) {
+            warnings.push(f!("Distribution {id} has a negative energy"));
+        }
+
+        match sp_cards.iter().find(|(other, _)| other == id) {
+            Some((_, probabilities)) => {
+                if probabilities.len() != entries.len() {
+                    warnings.push(f!(
+                        "Distribution {id} has {} SI entries but {} SP entries",
+                        entries.len(),
+                        probabilities.len()
+                    ));
+                }
+
+                if probabilities.iter().any(|p| *p < 0.0) {
+                    warnings.push(f!("Distribution {id} has a negative probability"));
+                } else if probabilities.iter().sum::<f64>() <= 0.0 {
+                    warnings.push(f!("Distribution {id} probabilities sum to zero"));
+                }
+            }
+            None => warnings.push(f!("Distribution {id} has no SP card")),
+        }
+    }
+
+    warnings
+}
+
+/// Sum of every SP card, checked against the expected total where known
+///
+/// MCNP normalises each distribution, so the sums are not expected to be 1.
+/// Instead, the discrete lines of a nuclide sum to its particles per decay
+/// and the master distribution to the total emission rate. Returns a line
+/// for every SP card, and whether it is within `SP_TOTAL_TOLERANCE`.
+pub fn sp_totals(sources: &[Source], cards: &str, config: &McnpConfig) -> Vec<(String, bool)> {
+    let sources = prepared_sources(sources, config);
+    let discrete = config.histogram.is_none() && !config.continuous && !config.consolidate;
+
+    let (_, sp_cards) = distribution_cards(cards);
+    sp_cards
+        .iter()
+        .map(|(id, probabilities)| {
+            let sum = probabilities.iter().sum::<f64>();
+
+            let expected = id
+                .parse::<usize>()
+                .ok()
+                .filter(|_| discrete)
+                .and_then(|n| n.checked_sub(config.id))
+                .and_then(|i| match i {
+                    0 => Some((
+                        "Master".to_string(),
+                        sources.iter().map(Source::photon_yield_per_second).sum(),
+                    )),
+                    _ => sources
+                        .get(i - 1)
+                        .map(|s| (s.fispact_name.clone(), s.norm())),
+                });
+
+            match expected {
+                Some((name, expected)) if expected > 0.0 => {
+                    let ok = ((sum - expected) / expected).abs() <= SP_TOTAL_TOLERANCE;
+                    (f!("{name} SP sum = {sum:.6} (should be {expected:.6})"), ok)
+                }
+                _ => (f!("Distribution {id} SP sum = {sum:.6}"), true),
+            }
+        })
+        .collect()
+}
+
+/// Distribution number, option letter, and entries of an SI card
+type SiCard = (String, String, Vec<f64>);
+
+/// Distribution number and entries of an SP card
+type SpCard = (String, Vec<f64>);
+
+/// SI and SP cards of every distribution, with continuation lines joined
+fn distribution_cards(cards: &str) -> (Vec<SiCard>, Vec<SpCard>) {
+    // join continuation lines onto their cards, ignoring comments
+    let mut joined: Vec<String> = Vec::new();
+    for line in cards.lines() {
+        let line = line.split('$').next().unwrap_or_default();
+        let is_comment =
+            line.trim().eq_ignore_ascii_case("c") || line.to_lowercase().starts_with("c ");
+        if is_comment || line.trim().is_empty() {
+            continue;
+        }
+
+        match (line.starts_with("     "), joined.last_mut()) {
+            (true, Some(card)) => *card += line,
+            _ => joined.push(line.to_string()),
+        }
+    }
+
+    let mut si_cards = Vec::new();
+    let mut sp_cards = Vec::new();
+    for card in &joined {
+        let mut words = card.split_whitespace();
+        let Some(name) = words.next().map(|w| w.to_lowercase()) else {
+            continue;
+        };
+
+        let (option, entries) = card_entries(words);
+        if let Some(id) = name.strip_prefix("si") {
+            si_cards.push((id.to_string(), option, entries));
+        } else if let Some(id) = name.strip_prefix("sp") {
+            sp_cards.push((id.to_string(), entries));
+        }
+    }
+
+    (si_cards, sp_cards)
+}
+
+/// Split card entries into the optional leading option letter and values
+fn card_entries<'a>(words: impl Iterator<Item = &'a str>) -> (String, Vec<f64>) {
+    let mut option = String::new();
+    let mut entries = Vec::new();
+
+    for word in words {
+        match word.parse::<f64>() {
+            Ok(value) => entries.push(value),
+            Err(_) => option = word.to_lowercase(),
+        }
+    }
+
+    (option, entries)
+}
+
+/// Lay out a card in the chosen format
+///
+/// Cards with `$` comments are always wrapped, since every entry with its
+/// comment is already too long to share a line.
+fn format_card(card: String, mode: McnpFormatMode) -> String {
+    if mode == McnpFormatMode::Wrapped || card.contains('$') {
+        return wrap_text(card, MAX_LINE_WIDTH, "        ");
+    }
+
+    let mut words = card.split_whitespace().peekable();
+    let mut head = words.next().unwrap_or_default().to_string();
+    if let Some(option) = words.next_if(|w| w.chars().all(|c| c.is_ascii_alphabetic())) {
+        head += &f!(" {option}");
+    }
+
+    std::iter::once(head)
+        .chain(words.map(|w| f!("        {w}")))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
 fn wrap_text(text: String, width: usize, subsequent_indent: &str) -> String {
     let options = textwrap::Options::new(width)
         .initial_indent("")
@@ -123,3 +1533,47 @@ fn wrap_text(text: String, width: usize, subsequent_indent: &str) -> String {
         .break_words(false);
     textwrap::fill(&text, options)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn energy_significant_figures() {
+        let energy = 1173.228 * KEV_TO_MEV;
+        assert_eq!(format_mev(energy, 4), "1.173e+00");
+        assert_eq!(format_mev(energy, 2), "1.2e+00");
+        assert_eq!(format_mev(energy, 6), "1.17323e+00");
+    }
+
+    #[test]
+    fn nps_from_dose_per_particle() {
+        // 1 in 1000 histories score, each needing 400 for a 5% error
+        assert!(nps_suggestion(5.0e-15).contains("c Suggested: nps 4.0e+05"));
+        assert!(nps_suggestion(0.0).is_empty());
+    }
+
+    #[test]
+    fn histogram_single_line() {
+        let (edges, weights) = histogram(&[(0.01, 0.5)], 0.1).unwrap();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0], 0.0);
+        assert!((edges[1] - 0.06).abs() < 1e-6);
+        assert_eq!(weights, vec![0.5]);
+    }
+
+    #[test]
+    fn histogram_merges_close_lines() {
+        let (edges, weights) = histogram(&[(1.0, 0.25), (1.02, 0.25), (1.5, 0.5)], 0.1).unwrap();
+        assert_eq!(edges.len(), weights.len() + 1);
+        assert!(edges.iter().all(|e| e.is_finite() && *e >= 0.0));
+        assert_eq!(weights.first(), Some(&0.5));
+        assert_eq!(weights.last(), Some(&0.5));
+    }
+
+    #[test]
+    fn histogram_without_lines_or_width() {
+        assert!(histogram(&[], 0.1).is_none());
+        assert!(histogram(&[(1.0, 1.0)], 0.0).is_none());
+    }
+}
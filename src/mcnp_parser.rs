@@ -0,0 +1,176 @@
+//! Basic reader for source distributions in MCNP input files
+//!
+//! Only the cards written by fisdef are understood, i.e. an `sdef` card, an
+//! `si S` master distribution over nuclides, and the `sc`/`si`/`sp` cards of
+//! each nuclide distribution. Everything else in the file is ignored.
+
+// standard lib
+use std::path::Path;
+
+// external
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+
+/// A single `si`/`sp` card pair and its comment
+#[derive(Debug, Clone)]
+pub struct Distribution {
+    /// Distribution number
+    pub id: usize,
+    /// Text of the `sc` card, if any
+    pub comment: Option<String>,
+    /// Option letter of the `si` card, e.g. L or H
+    pub si_option: Option<String>,
+    /// Entries of the `si` card
+    pub si: Vec<String>,
+    /// Option letter of the `sp` card, e.g. D
+    pub sp_option: Option<String>,
+    /// Entries of the `sp` card
+    pub sp: Vec<String>,
+}
+
+/// Master distribution and every distribution it samples from
+#[derive(Debug, Clone)]
+pub struct ParsedSource {
+    /// Particle designator of the `sdef` card
+    pub particle: Option<String>,
+    /// Distribution number and absolute weight of each sub-source
+    pub master: Vec<(usize, f64)>,
+    /// Nuclide distributions in the order they are sampled
+    pub distributions: Vec<Distribution>,
+}
+
+impl ParsedSource {
+    /// Total strength of the source, i.e. the sum of master weights
+    pub fn strength(&self) -> f64 {
+        self.master.iter().map(|(_, w)| w).sum()
+    }
+}
+
+/// Read the source distributions from an MCNP input file
+pub fn read(path: &Path) -> Result<ParsedSource> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Unable to read {}", path.display()))?;
+    parse(&text).with_context(|| format!("Invalid source in {}", path.display()))
+}
+
+/// Parse the source distributions from MCNP input text
+pub fn parse(text: &str) -> Result<ParsedSource> {
+    let card_re = Regex::new(r"^(?i)(si|sp|sc)(\d+)\s*(.*)$")?;
+    let par_re = Regex::new(r"(?i)\bpar\s*=\s*(\w+)")?;
+
+    let mut particle = None;
+    let mut comments = Vec::new();
+    let mut si_cards = Vec::new();
+    let mut sp_cards = Vec::new();
+
+    for card in join_cards(text) {
+        if card.to_lowercase().starts_with("sdef") {
+            particle = par_re.captures(&card).map(|c| c[1].to_string());
+            continue;
+        }
+
+        let Some(captures) = card_re.captures(&card) else {
+            continue;
+        };
+
+        let id = captures[2].parse::<usize>()?;
+        let body = captures[3].trim().to_string();
+        match captures[1].to_lowercase().as_str() {
+            "sc" => comments.push((id, body)),
+            "si" => si_cards.push((id, split_option(&body))),
+            _ => sp_cards.push((id, split_option(&body))),
+        }
+    }
+
+    // the master distribution is the only one sampling other distributions
+    let Some((master_id, (_, master_ids))) = si_cards
+        .iter()
+        .find(|(_, (option, _))| {
+            option
+                .as_deref()
+                .is_some_and(|o| o.eq_ignore_ascii_case("s"))
+        })
+        .cloned()
+    else {
+        bail!("No master 'si S' distribution found")
+    };
+
+    let Some((_, (_, weights))) = sp_cards.iter().find(|(id, _)| *id == master_id) else {
+        bail!("No sp card found for master distribution {master_id}")
+    };
+
+    if master_ids.len() != weights.len() {
+        bail!("Master distribution {master_id} has mismatched si and sp entries")
+    }
+
+    let mut master = Vec::new();
+    let mut distributions = Vec::new();
+    for (id, weight) in master_ids.iter().zip(weights) {
+        let id = id.parse::<usize>()?;
+        master.push((id, weight.parse::<f64>()?));
+
+        let Some((_, (si_option, si))) = si_cards.iter().find(|(i, _)| *i == id) else {
+            bail!("No si card found for distribution {id}")
+        };
+        let Some((_, (sp_option, sp))) = sp_cards.iter().find(|(i, _)| *i == id) else {
+            bail!("No sp card found for distribution {id}")
+        };
+
+        distributions.push(Distribution {
+            id,
+            comment: comments
+                .iter()
+                .find(|(i, _)| *i == id)
+                .map(|(_, c)| c.clone()),
+            si_option: si_option.clone(),
+            si: si.clone(),
+            sp_option: sp_option.clone(),
+            sp: sp.clone(),
+        });
+    }
+
+    Ok(ParsedSource {
+        particle,
+        master,
+        distributions,
+    })
+}
+
+/// Join continuation lines onto their cards, dropping comments
+fn join_cards(text: &str) -> Vec<String> {
+    let mut cards: Vec<String> = Vec::new();
+
+    for line in text.lines() {
+        let lower = line.to_lowercase();
+        if lower.trim() == "c" || lower.starts_with("c ") || line.trim().is_empty() {
+            continue;
+        }
+
+        // sc cards are comments themselves, so keep any '$' in them
+        let line = match lower.starts_with("sc") {
+            true => line,
+            false => line.split('$').next().unwrap_or_default(),
+        };
+
+        match (line.starts_with("     "), cards.last_mut()) {
+            (true, Some(card)) => {
+                card.push(' ');
+                card.push_str(line.trim());
+            }
+            _ => cards.push(line.trim_end().to_string()),
+        }
+    }
+
+    cards
+}
+
+/// Split card entries into the optional leading option letter and values
+fn split_option(body: &str) -> (Option<String>, Vec<String>) {
+    let mut words = body.split_whitespace().peekable();
+
+    let option = words
+        .next_if(|w| w.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(|w| w.to_string());
+
+    (option, words.map(|w| w.to_string()).collect())
+}
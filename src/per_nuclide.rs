@@ -0,0 +1,73 @@
+//! CSV files of activity against time, one per nuclide
+//!
+//! Each file has one row per interval in which the nuclide had decay data:
+//!
+//! ```text
+//! interval_index,cool_time_s,activity_bq,n_records,photon_strength
+//! 1,0.00000e+00,1.20000e+10,2,2.39765e+10
+//! ```
+
+// internal
+use crate::fileio::{open_output, ExistsAction};
+use crate::source::Source;
+
+// neutronics toolbox
+use ntools::fispact::Inventory;
+use ntools::utils::{f, ValueExt};
+
+// standard lib
+use std::io::Write;
+use std::path::Path;
+
+// external
+use anyhow::Result;
+use log::debug;
+
+/// Write `{nuclide}.csv` into `dir` for every nuclide found in any interval
+///
+/// The strength is the emission rate of the chosen radiation type, i.e.
+/// the activity multiplied by the particles per decay.
+pub fn per_nuclide_output(
+    all_sources: &[(usize, Vec<Source>)],
+    inventory: &Inventory,
+    dir: &Path,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let mut names = all_sources
+        .iter()
+        .flat_map(|(_, sources)| sources.iter().map(|s| s.fispact_name.clone()))
+        .collect::<Vec<String>>();
+    names.sort();
+    names.dedup();
+    debug!("Writing activity histories of {} nuclides", names.len());
+
+    for name in names {
+        let path = dir.join(&name);
+        let Some(mut f) = open_output(&path, "csv", &f!("{name}.csv"), compress, exists)? else {
+            continue;
+        };
+        writeln!(
+            f,
+            "interval_index,cool_time_s,activity_bq,n_records,photon_strength"
+        )?;
+
+        for (index, sources) in all_sources {
+            let Some(source) = sources.iter().find(|s| s.fispact_name == name) else {
+                continue;
+            };
+
+            writeln!(
+                f,
+                "{index},{},{},{},{}",
+                inventory.intervals[*index].cooling_time.sci(5, 2),
+                source.fispact_activity.sci(5, 2),
+                source.iaea_records.len(),
+                source.photon_yield_per_second().sci(5, 2)
+            )?;
+        }
+        f.finish()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! Bar chart of the source spectrum for a quick visual check
+//!
+//! Only available with the `plot` feature, since `plotters` is a heavy
+//! dependency that most users do not need.
+
+// internal
+use crate::source::Source;
+
+// standard lib
+use std::path::Path;
+
+// external
+use anyhow::{bail, Result};
+use plotters::prelude::*;
+
+/// Number of distinct colours before they are reused
+const N_COLOURS: usize = 12;
+
+/// Number of nuclides named in the legend
+const N_LEGEND: usize = 5;
+
+/// Draw each line as a bar of emission rate against energy
+///
+/// Every nuclide has its own colour, and the most active are named in the
+/// legend.
+pub fn draw(sources: &[Source], path: &Path) -> Result<()> {
+    // emission rate [particles/s] of every line, grouped by nuclide
+    let lines = sources
+        .iter()
+        .map(|s| {
+            s.iaea_records
+                .iter()
+                .filter_map(|r| Some((r.energy?, s.fispact_activity * r.intensity? as f64 * 1e-2)))
+                .filter(|(_, rate)| *rate > 0.0)
+                .collect::<Vec<(f32, f64)>>()
+        })
+        .collect::<Vec<Vec<(f32, f64)>>>();
+
+    let all = lines.iter().flatten().collect::<Vec<&(f32, f64)>>();
+    if all.is_empty() {
+        bail!("No lines to plot")
+    }
+
+    let e_max = all.iter().map(|(e, _)| *e).fold(0.0, f32::max);
+    let r_max = all.iter().map(|(_, r)| *r).fold(0.0, f64::max);
+    let r_min = all.iter().map(|(_, r)| *r).fold(f64::INFINITY, f64::min);
+
+    // most active nuclides for the legend
+    let mut ranked = (0..sources.len()).collect::<Vec<usize>>();
+    ranked.sort_by(|a, b| {
+        sources[*b]
+            .fispact_activity
+            .total_cmp(&sources[*a].fispact_activity)
+    });
+    ranked.truncate(N_LEGEND);
+
+    let root = BitMapBackend::new(path, (1200, 700)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let y_floor = r_min * 0.5;
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Source spectrum", ("sans-serif", 24))
+        .margin(15)
+        .x_label_area_size(45)
+        .y_label_area_size(80)
+        .build_cartesian_2d(
+            0.0_f32..(e_max * 1.05),
+            (y_floor..(r_max * 2.0)).log_scale(),
+        )?;
+
+    chart
+        .configure_mesh()
+        .x_desc("Energy [keV]")
+        .y_desc("Intensity [particles/s]")
+        .y_label_formatter(&|y| format!("{y:.0e}"))
+        .draw()?;
+
+    for (i, (source, lines)) in sources.iter().zip(&lines).enumerate() {
+        let colour = Palette99::pick(i % N_COLOURS).to_rgba();
+        let series = chart.draw_series(lines.iter().map(|(energy, rate)| {
+            PathElement::new(
+                vec![(*energy, y_floor), (*energy, *rate)],
+                colour.stroke_width(2),
+            )
+        }))?;
+
+        if ranked.contains(&i) {
+            series
+                .label(source.fispact_name.clone())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], colour));
+        }
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,53 @@
+//! Pre-warm the IAEA cache for offline runs
+
+// internal
+use crate::cache;
+
+// neutronics toolbox
+use ntools::fispact::Inventory;
+use ntools::iaea;
+
+// standard lib
+use std::path::Path;
+
+// external
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info};
+use rayon::prelude::*;
+
+/// Fetch decay data for every unique nuclide across all intervals
+///
+/// Anything already in the cache is skipped, and the remainder are fetched
+/// from the IAEA API in parallel.
+pub fn prefetch_all(inventory: &Inventory, radtype: iaea::RadType, cache_dir: &Path) {
+    let mut names = inventory
+        .intervals
+        .iter()
+        .flat_map(|i| i.unstable_nuclides())
+        .map(|n| n.name())
+        .collect::<Vec<String>>();
+    names.sort();
+    names.dedup();
+
+    let nuclides = names
+        .into_iter()
+        .filter_map(|name| iaea::Nuclide::try_from(name).ok())
+        .filter(|n| !cache::contains(cache_dir, n, radtype))
+        .collect::<Vec<iaea::Nuclide>>();
+
+    info!("Fetching {radtype:?} data for {} nuclides", nuclides.len());
+
+    let progress = ProgressBar::new(nuclides.len() as u64);
+    if let Ok(style) = ProgressStyle::with_template("{bar:40} {pos}/{len} {msg}") {
+        progress.set_style(style);
+    }
+
+    nuclides.par_iter().for_each(|n| {
+        if cache::fetch_with_cache(n, radtype, cache_dir).is_none() {
+            debug!("No {radtype:?} records for {}", n.name_with_state());
+        }
+        progress.inc(1);
+    });
+
+    progress.finish_with_message("done");
+}
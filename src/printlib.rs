@@ -0,0 +1,147 @@
+//! Reader for columnar PRINTLIB-style nuclide activity tables
+//!
+//! The table is headed by the nuclide, atomic number, mass number, and
+//! half-life columns, followed by one activity column per interval labelled
+//! with its cooling time [s], for example:
+//!
+//! ```text
+//! NUCLIDE  Z   A   HALF_LIFE   0.0        3.6e+03    8.64e+04
+//! Co60     27  60  1.663e+08   1.20e+10   1.20e+10   1.19e+10
+//! Mn56     25  56  9.284e+03   4.50e+11   3.45e+11   6.13e+08
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored. Activities are in Bq
+//! and half-lives in seconds, and every row must have one value per cooling
+//! time. The interval activity is the sum over all rows.
+//!
+//! The table holds activities only, so atoms, grams, masses, doses, and
+//! irradiation times are all zero. The atomic number is checked for being an
+//! integer but is otherwise unused.
+
+// internal
+use crate::fispact_out::{self, RawInterval, RawNuclide};
+
+// neutronics toolbox
+use ntools::fispact::Inventory;
+use ntools::utils::f;
+
+// standard lib
+use std::fs;
+use std::path::Path;
+
+// external
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+/// Number of columns before the activities
+const N_FIXED_COLUMNS: usize = 4;
+
+/// Read the inventory data from a PRINTLIB table
+pub fn read(path: &Path) -> Result<Inventory> {
+    let text =
+        fs::read_to_string(path).with_context(|| f!("Unable to read {:?}", path.display()))?;
+    from_str(&text)
+}
+
+/// Read the inventory data from the text of a PRINTLIB table
+pub fn from_str(text: &str) -> Result<Inventory> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(n, l)| (n + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'));
+
+    let Some((_, header)) = lines.next() else {
+        bail!("No PRINTLIB table found")
+    };
+
+    let mut intervals = parse_header(header).context("Invalid PRINTLIB header")?;
+    debug!("Found {} inventory intervals", intervals.len());
+
+    for (n, line) in lines {
+        let (nuclide, activities) =
+            parse_row(line, intervals.len()).with_context(|| f!("Invalid row on line {n}"))?;
+
+        for (interval, activity) in intervals.iter_mut().zip(activities) {
+            interval.activity += activity;
+            interval.nuclides.push(RawNuclide {
+                activity,
+                ..nuclide.clone()
+            });
+        }
+    }
+
+    fispact_out::to_inventory(&intervals)
+}
+
+/// Create an empty interval for each cooling time given in the header
+fn parse_header(line: &str) -> Result<Vec<RawInterval>> {
+    let columns = line.split_whitespace().collect::<Vec<&str>>();
+    if columns.len() <= N_FIXED_COLUMNS || !columns[0].eq_ignore_ascii_case("nuclide") {
+        bail!("Expected \"NUCLIDE Z A HALF_LIFE\" followed by cooling times")
+    }
+
+    columns[N_FIXED_COLUMNS..]
+        .iter()
+        .map(|c| {
+            let cooling_time = c
+                .parse::<f64>()
+                .with_context(|| f!("Bad cooling time \"{c}\""))?;
+            Ok(RawInterval {
+                cooling_time,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Parse a nuclide row, e.g. `Co60m  27  60  6.28e+02  1.0e+10 ...`
+fn parse_row(line: &str, n_intervals: usize) -> Result<(RawNuclide, Vec<f64>)> {
+    let columns = line.split_whitespace().collect::<Vec<&str>>();
+    if columns.len() != N_FIXED_COLUMNS + n_intervals {
+        bail!(
+            "Expected {} columns, found {}",
+            N_FIXED_COLUMNS + n_intervals,
+            columns.len()
+        )
+    }
+
+    // name is the element, mass number, and an optional isomer state suffix
+    let name = columns[0];
+    let split = name
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(name.len());
+    let (element, isotope) = name.split_at(split);
+    let state = isotope.trim_start_matches(|c: char| c.is_ascii_digit());
+
+    let mass_number = columns[2]
+        .parse::<u32>()
+        .with_context(|| f!("Bad mass number \"{}\"", columns[2]))?;
+    if !isotope.starts_with(&mass_number.to_string()) {
+        warn!("Mass number {mass_number} does not match nuclide {name}");
+    }
+    columns[1]
+        .parse::<u32>()
+        .with_context(|| f!("Bad atomic number \"{}\"", columns[1]))?;
+
+    let half_life = columns[3]
+        .parse::<f64>()
+        .with_context(|| f!("Bad half-life \"{}\"", columns[3]))?;
+
+    let activities = columns[N_FIXED_COLUMNS..]
+        .iter()
+        .map(|c| c.parse::<f64>().with_context(|| f!("Bad activity \"{c}\"")))
+        .collect::<Result<Vec<f64>>>()?;
+
+    let nuclide = RawNuclide {
+        element: element.to_string(),
+        isotope: mass_number,
+        state: state.to_string(),
+        atoms: 0.0,
+        grams: 0.0,
+        activity: 0.0,
+        half_life,
+    };
+
+    Ok((nuclide, activities))
+}
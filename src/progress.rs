@@ -0,0 +1,98 @@
+//! Machine-readable progress for runs without a terminal
+//!
+//! The progress file is rewritten after every interval, for example:
+//!
+//! ```text
+//! {"total":4,"completed":2,"current_interval":3,"elapsed_s":1.52,"last_file":"fisdef_3"}
+//! ```
+
+// internal
+use crate::fileio::{self, ExistsAction};
+
+// neutronics toolbox
+use ntools::utils::f;
+
+// standard lib
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// external
+use anyhow::{Context, Result};
+use log::trace;
+use serde_json::json;
+
+/// Progress through the selected intervals of a single run
+#[derive(Debug)]
+pub struct Progress {
+    path: PathBuf,
+    total: usize,
+    completed: usize,
+    last_file: Option<String>,
+    start: Instant,
+}
+
+impl Progress {
+    /// Start tracking `total` intervals, written to the file at `path`
+    ///
+    /// The file is created straight away, so that an existing one is handled
+    /// by `exists` before any interval. Nothing is tracked if it is skipped.
+    pub fn new(path: &Path, total: usize, exists: ExistsAction) -> Result<Option<Self>> {
+        let Some(mut f) = fileio::open_named(path, exists)? else {
+            return Ok(None);
+        };
+
+        let progress = Self {
+            path: path.to_path_buf(),
+            total,
+            completed: 0,
+            last_file: None,
+            start: Instant::now(),
+        };
+
+        f.write_all(progress.to_json(None).to_string().as_bytes())?;
+        f.finish()?;
+        Ok(Some(progress))
+    }
+
+    /// Remove an interval from the total without rewriting the progress file
+    pub fn skip(&mut self) {
+        self.total = self.total.saturating_sub(1);
+    }
+
+    /// Record a completed interval and rewrite the progress file
+    ///
+    /// The output prefix is given for intervals that wrote any files, and the
+    /// most recent one is kept for those that did not.
+    pub fn update(&mut self, index: usize, output: Option<&Path>) -> Result<()> {
+        self.completed += 1;
+        if let Some(output) = output {
+            self.last_file = Some(output.display().to_string());
+        }
+
+        let progress = self.to_json(Some(index));
+        trace!("Progress: {progress}");
+
+        // write then rename, so the file is never seen half written
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        fs::write(&tmp, progress.to_string())
+            .with_context(|| f!("Unable to write progress to {:?}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| f!("Unable to replace {:?}", self.path.display()))
+    }
+
+    /// Current progress, with the index of the interval just completed
+    fn to_json(&self, index: Option<usize>) -> serde_json::Value {
+        json!({
+            "total": self.total,
+            "completed": self.completed,
+            "current_interval": index,
+            "elapsed_s": self.start.elapsed().as_secs_f64(),
+            "last_file": self.last_file,
+        })
+    }
+}
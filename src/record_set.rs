@@ -0,0 +1,57 @@
+//! Common operations on the decay records of a nuclide
+//!
+//! `RecordSet` belongs to `ntools`, so these are provided by an extension
+//! trait in the same way as `ValueExt` and `OptionExt`.
+
+// neutronics toolbox
+use ntools::iaea::{Record, RecordSet};
+use ntools::utils::OptionExt;
+
+// standard lib
+use std::collections::HashMap;
+
+/// Helper methods for a set of decay records
+pub trait RecordSetExt {
+    /// Sum of every known intensity [%]
+    fn total_intensity(&self) -> f32;
+
+    /// Record of the most intense line, ignoring unknown intensities
+    fn dominant(&self) -> Option<&Record>;
+
+    /// Records grouped by their decay mode, e.g. "B-"
+    fn by_decay_mode(&self) -> HashMap<String, Vec<&Record>>;
+
+    /// Copy of every record at or above an energy [keV]
+    fn above_energy(&self, kev: f32) -> RecordSet;
+}
+
+impl RecordSetExt for RecordSet {
+    fn total_intensity(&self) -> f32 {
+        self.iter().filter_map(|r| r.intensity).sum()
+    }
+
+    fn dominant(&self) -> Option<&Record> {
+        self.iter()
+            .filter_map(|r| Some((r, r.intensity?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(r, _)| r)
+    }
+
+    fn by_decay_mode(&self) -> HashMap<String, Vec<&Record>> {
+        let mut groups: HashMap<String, Vec<&Record>> = HashMap::new();
+        for record in self {
+            groups
+                .entry(record.decay_mode.display())
+                .or_default()
+                .push(record);
+        }
+        groups
+    }
+
+    fn above_energy(&self, kev: f32) -> RecordSet {
+        self.iter()
+            .filter(|r| r.energy.unwrap_or(0.0) >= kev)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,149 @@
+//! Self-contained HTML summary report over all selected intervals
+
+// internal
+use crate::cli::Cli;
+use crate::fileio::open_output;
+use crate::source::Source;
+use crate::table::{Table, TableConfig};
+
+// neutronics toolbox
+use ntools::fispact::Inventory;
+use ntools::utils::{f, ValueExt};
+
+// standard lib
+use std::io::Write;
+use std::path::PathBuf;
+
+// external
+use anyhow::{Context, Result};
+use minijinja::{context, Environment};
+use serde::Serialize;
+
+/// Report template, embedded at compile time
+const TEMPLATE: &str = include_str!("templates/report.html");
+
+/// Number of nuclides shown in the activity chart
+const N_TOP: usize = 10;
+
+/// Row of the interval summary table
+#[derive(Serialize)]
+struct SummaryRow {
+    index: usize,
+    irradiation_time: String,
+    cooling_time: String,
+    mass: String,
+    dose: String,
+    activity: String,
+}
+
+/// Statistics and tables for a single interval
+#[derive(Serialize)]
+struct IntervalSection {
+    index: usize,
+    n_sources: usize,
+    activity: String,
+    particles: String,
+    top: Vec<Bar>,
+    table: String,
+}
+
+/// Single entry of the top nuclide chart
+#[derive(Serialize)]
+struct Bar {
+    name: String,
+    activity: String,
+    percent: f64,
+}
+
+/// Writes the HTML report to `<name>_report.html`
+pub fn write(inventory: &Inventory, intervals: &[(usize, Vec<Source>)], cli: &Cli) -> Result<()> {
+    let mut path = PathBuf::from(&cli.output);
+    path.set_file_name(f!("{}_report", cli.output_name()));
+
+    let Some(mut f) = open_output(
+        &path,
+        "html",
+        "report.html",
+        cli.compress_output,
+        cli.output_exists_action,
+    )?
+    else {
+        return Ok(());
+    };
+    let html = render(inventory, intervals, cli)?;
+    f.write_all(html.as_bytes())?;
+    f.finish()
+}
+
+/// Fill the report template
+fn render(inventory: &Inventory, intervals: &[(usize, Vec<Source>)], cli: &Cli) -> Result<String> {
+    let summary = inventory
+        .intervals
+        .iter()
+        .enumerate()
+        .map(|(index, i)| SummaryRow {
+            index,
+            irradiation_time: i.irradiation_time.sci(2, 2),
+            cooling_time: i.cooling_time.sci(2, 2),
+            mass: i.mass.sci(2, 2),
+            dose: (i.dose.rate * 1e6).sci(2, 2),
+            activity: i.activity.sci(2, 2),
+        })
+        .collect::<Vec<SummaryRow>>();
+
+    let config = TableConfig::from(cli);
+    let intervals = intervals
+        .iter()
+        .map(|(index, sources)| section(*index, sources, &config))
+        .collect::<Vec<IntervalSection>>();
+
+    let mut env = Environment::new();
+    env.add_template("report.html", TEMPLATE)
+        .context("Invalid report template")?;
+
+    env.get_template("report.html")?
+        .render(context! {
+            title => cli.path.as_deref().unwrap_or("stdin"),
+            version => env!("CARGO_PKG_VERSION"),
+            rad => cli.rad.name(),
+            summary => summary,
+            intervals => intervals,
+        })
+        .context("Unable to render report")
+}
+
+/// Collect the statistics for one interval
+fn section(index: usize, sources: &[Source], config: &TableConfig) -> IntervalSection {
+    let activity = sources.iter().map(|s| s.fispact_activity).sum::<f64>();
+    let particles = sources
+        .iter()
+        .map(Source::photon_yield_per_second)
+        .sum::<f64>();
+
+    let mut sorted = sources.iter().collect::<Vec<&Source>>();
+    sorted.sort_by(|a, b| b.fispact_activity.total_cmp(&a.fispact_activity));
+    let max = sorted.first().map_or(0.0, |s| s.fispact_activity);
+
+    let top = sorted
+        .iter()
+        .take(N_TOP)
+        .map(|s| Bar {
+            name: s.fispact_name.clone(),
+            activity: s.fispact_activity.sci(2, 2),
+            percent: if max > 0.0 {
+                100.0 * s.fispact_activity / max
+            } else {
+                0.0
+            },
+        })
+        .collect();
+
+    IntervalSection {
+        index,
+        n_sources: sources.len(),
+        activity: activity.sci(2, 2),
+        particles: particles.sci(2, 2),
+        top,
+        table: Table::new(sources, config).to_string(),
+    }
+}
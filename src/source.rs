@@ -1,21 +1,85 @@
 // intenral
-use crate::cli::SortProperty;
+use crate::cache;
+use crate::cli::{NuclideSort, SortProperty};
+use crate::record_set::RecordSetExt;
+use crate::wrappers::CliRadType;
 
 // Neutronics toolbox
 use ntools::iaea::{self, IsomerState, Record, RecordSet};
-use ntools::utils::OptionExt;
+use ntools::utils::{OptionExt, ValueExt};
+
+// standard lib
+use std::collections::HashMap;
+use std::path::Path;
 
 // external
-use anyhow::Result;
-use log::{debug, trace};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use log::{debug, trace, warn};
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 
+/// Decay records already loaded during this run
+///
+/// Keyed by nuclide and radiation type, so that nuclides shared between
+/// intervals are only read or fetched once.
+#[derive(Debug, Default)]
+pub struct NuclideCache {
+    records: HashMap<String, Option<RecordSet>>,
+}
+
+impl NuclideCache {
+    /// Records for a nuclide, loading them on the first request only
+    fn get_or_load<F>(
+        &mut self,
+        nuclide: &iaea::Nuclide,
+        radtype: iaea::RadType,
+        load: F,
+    ) -> Option<RecordSet>
+    where
+        F: FnOnce() -> Option<RecordSet>,
+    {
+        let key = format!("{}_{radtype:?}", nuclide.name_with_state());
+        if let Some(records) = self.records.get(&key) {
+            trace!(
+                "Reusing {radtype:?} records for {}",
+                nuclide.name_with_state()
+            );
+            return records.clone();
+        }
+
+        let records = load();
+        self.records.insert(key, records.clone());
+        records
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Source {
     pub fispact_name: String,
     pub fispact_activity: f64,
     pub iaea_nuclide: iaea::Nuclide,
     pub iaea_records: RecordSet,
+    /// Added as a daughter by `--chain-depth` rather than found by FISPACT
+    pub from_chain: bool,
+}
+
+/// Single decay line of a source, flattened for tabular processing
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SourceRow {
+    pub fispact_name: String,
+    pub iaea_name: String,
+    pub activity_bq: f64,
+    pub energy_kev: f32,
+    pub intensity_pct: f32,
+    pub branching_ratio: Option<f32>,
+    pub half_life_s: Option<f32>,
+    pub decay_mode: String,
+}
+
+impl From<&Source> for Vec<SourceRow> {
+    fn from(source: &Source) -> Self {
+        source.to_rows()
+    }
 }
 
 impl Serialize for Source {
@@ -24,7 +88,7 @@ impl Serialize for Source {
         S: Serializer,
     {
         // Create a struct serializer
-        let mut state = serializer.serialize_struct("Source", 5)?;
+        let mut state = serializer.serialize_struct("Source", 6)?;
 
         state.serialize_field("name_fispact", &self.fispact_name)?;
         state.serialize_field("name_iaea", &self.iaea_nuclide.name_with_state())?;
@@ -35,11 +99,43 @@ impl Serialize for Source {
 
         state.serialize_field("energy", &energy)?;
         state.serialize_field("intensity", &intensity)?;
+        state.serialize_field("from_chain", &self.from_chain)?;
 
         state.end()
     }
 }
 
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) | activity: {} Bq{} | {} records | norm: {}",
+            self.fispact_name,
+            self.iaea_nuclide.name_with_state(),
+            self.fispact_activity.sci(3, 2),
+            match self.from_chain {
+                true => " (computed from chain)",
+                false => "",
+            },
+            self.iaea_records.len(),
+            self.norm().sci(3, 2)
+        )
+    }
+}
+
+/// Collection of sources with a compact one-line summary of each
+pub struct Sources(pub Vec<Source>);
+
+impl std::fmt::Display for Sources {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{} sources:", self.0.len())?;
+        for s in &self.0 {
+            writeln!(f, "   {s}")?;
+        }
+        Ok(())
+    }
+}
+
 impl PartialEq for Source {
     fn eq(&self, other: &Self) -> bool {
         self.fispact_name == other.fispact_name && self.iaea_nuclide == other.iaea_nuclide
@@ -47,21 +143,388 @@ impl PartialEq for Source {
 }
 
 impl Source {
+    /// Construct a source directly, bypassing the FISPACT to IAEA lookup
+    pub fn from_manual(
+        fispact_name: &str,
+        activity_bq: f64,
+        iaea_nuclide: iaea::Nuclide,
+        records: RecordSet,
+    ) -> Source {
+        Source {
+            fispact_name: fispact_name.to_string(),
+            fispact_activity: activity_bq,
+            iaea_nuclide,
+            iaea_records: records,
+            from_chain: false,
+        }
+    }
+
+    /// Construct a single-line source from a CSV row, by header name
+    ///
+    /// Requires `name`, `activity_bq`, `energy_kev`, and `intensity_pct`
+    /// columns, while `half_life_s` and `branching_ratio` are optional. Any
+    /// decay data not in the row are left unset, see `manual_record`.
+    pub fn from_csv_row(row: &csv::StringRecord, headers: &csv::StringRecord) -> Result<Source> {
+        let column = |key: &str| {
+            headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(key))
+                .and_then(|i| row.get(i))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        let required = |key: &str| column(key).with_context(|| format!("Missing \"{key}\""));
+        let optional = |key: &str| -> Result<Option<f32>> {
+            column(key)
+                .map(|v| {
+                    v.parse::<f32>()
+                        .with_context(|| format!("Bad {key} \"{v}\""))
+                })
+                .transpose()
+        };
+
+        let name = required("name")?;
+        let activity = required("activity_bq")?;
+        let energy = required("energy_kev")?;
+        let intensity = required("intensity_pct")?;
+
+        if column("decay_mode").is_some() {
+            debug!("Ignoring decay_mode for {name}, as it is not read from CSV");
+        }
+
+        let Ok(nuclide) = iaea::Nuclide::try_from(name.to_string()) else {
+            bail!("Could not convert \"{name}\" to a nuclide")
+        };
+
+        let mut record = manual_record(
+            energy
+                .parse()
+                .with_context(|| format!("Bad energy_kev \"{energy}\""))?,
+            intensity
+                .parse()
+                .with_context(|| format!("Bad intensity_pct \"{intensity}\""))?,
+        );
+        record.half_life = optional("half_life_s")?;
+        record.branching = optional("branching_ratio")?;
+
+        Ok(Source::from_manual(
+            name,
+            activity
+                .parse()
+                .with_context(|| format!("Bad activity_bq \"{activity}\""))?,
+            nuclide,
+            vec![record],
+        ))
+    }
+
     /// Normalisation factor for the decay data
     pub fn norm(&self) -> f64 {
-        (self
+        (self.iaea_records.total_intensity() / 100.0) as f64
+    }
+
+    /// Particles of the chosen radiation type emitted per second
+    ///
+    /// The activity [Bq] multiplied by the particles per decay from `norm`,
+    /// i.e. the photon yield for gamma and x-ray sources.
+    pub fn photon_yield_per_second(&self) -> f64 {
+        self.fispact_activity * self.norm()
+    }
+
+    /// Intensity of the strongest line
+    pub fn dominant_intensity(&self) -> Option<f32> {
+        self.iaea_records.dominant().and_then(|r| r.intensity)
+    }
+
+    /// Intensity of every record relative to the strongest line = 100%
+    ///
+    /// The records themselves are never rescaled, so MCNP weights and the
+    /// normalisation always use absolute intensities.
+    pub fn relative_intensity(&self) -> Vec<Option<f32>> {
+        let dominant = self.dominant_intensity().filter(|d| *d > 0.0);
+        self.iaea_records
+            .iter()
+            .map(|r| Some(100.0 * r.intensity? / dominant?))
+            .collect()
+    }
+
+    /// Uncertainty on `norm()` assuming independent record intensities
+    ///
+    /// Records without an intensity uncertainty in the IAEA data contribute
+    /// nothing, and `None` is returned if no record has one at all.
+    pub fn norm_uncertainty(&self) -> Option<f64> {
+        let variances = self
             .iaea_records
             .iter()
-            .fold(0.0, |acc, r| acc + r.intensity.unwrap_or(0.0))
-            / 100.0) as f64
+            .filter_map(|r| r.unc_i)
+            .map(|u| (u as f64 / 100.0).powi(2))
+            .collect::<Vec<f64>>();
+
+        match variances.is_empty() {
+            true => None,
+            false => Some(variances.iter().sum::<f64>().sqrt()),
+        }
     }
 
-    /// todo: Big mess of edge cases that neads cleaning up
-    pub fn find_records(&mut self, radtype: iaea::RadType, fetch: bool) {
-        let nuclide_records = match fetch {
-            false => iaea::load_nuclide(self.iaea_nuclide.clone(), radtype),
-            true => iaea::fetch_nuclide(self.iaea_nuclide.clone(), radtype),
+    /// One row per record with a known energy and intensity
+    pub fn to_rows(&self) -> Vec<SourceRow> {
+        self.iaea_records
+            .iter()
+            .filter_map(|r| {
+                Some(SourceRow {
+                    fispact_name: self.fispact_name.clone(),
+                    iaea_name: self.iaea_nuclide.name_with_state(),
+                    activity_bq: self.fispact_activity,
+                    energy_kev: r.energy?,
+                    intensity_pct: r.intensity?,
+                    branching_ratio: r.branching,
+                    half_life_s: r.half_life,
+                    decay_mode: r.decay_mode.display(),
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the nuclide is an excited isomeric state, e.g. Co60m
+    pub fn is_isomeric(&self) -> bool {
+        matches!(self.iaea_nuclide.state, IsomerState::Excited(_))
+    }
+
+    /// Excitation energy [keV] of an isomeric state from its records
+    pub fn isomer_energy(&self) -> Option<f32> {
+        match self.is_isomeric() {
+            true => self.iaea_records.iter().find_map(|r| r.p_energy),
+            false => None,
+        }
+    }
+
+    /// Name with the excitation energy of any isomer, e.g. "Co60m (E_iso = 58.6 keV)"
+    pub fn name_with_isomer_energy(&self) -> String {
+        match self.isomer_energy() {
+            Some(energy) => format!("{} (E_iso = {energy} keV)", self.fispact_name),
+            None => self.fispact_name.clone(),
+        }
+    }
+
+    /// Half-life [s] from the first record with a known half-life
+    pub fn half_life(&self) -> Option<f32> {
+        self.iaea_records.iter().find_map(|r| r.half_life)
+    }
+
+    /// Unique daughters of every record that are stable, in alphabetical order
+    ///
+    /// A daughter is taken to be stable if it has no decay data of any kind,
+    /// or if its half-life is longer than `STABLE_HALF_LIFE`.
+    pub fn stable_daughters(&self) -> Vec<String> {
+        let mut names = self
+            .iaea_records
+            .iter()
+            .map(|r| r.daughter_name())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        names.retain(|name| is_stable(name));
+        names
+    }
+
+    /// Unstable daughters of every decay mode, with their branching ratio [%]
+    ///
+    /// Daughters are taken from a single record set, the first that names
+    /// any. Gamma records are tried first as they follow every decay mode, and
+    /// the rest only for emitters without them, e.g. pure beta. Branching
+    /// ratios of separate decay modes to the same daughter are summed.
+    pub fn unstable_daughters(
+        &self,
+        fetch: bool,
+        cache_dir: Option<&Path>,
+        database: Option<&Path>,
+        pool: &mut NuclideCache,
+    ) -> Vec<(String, f32)> {
+        let order = [
+            CliRadType::Gamma,
+            CliRadType::BetaMinus,
+            CliRadType::BetaPlus,
+            CliRadType::Alpha,
+            CliRadType::Electron,
+            CliRadType::Xray,
+        ];
+
+        let records = order
+            .into_iter()
+            .map(|rad| {
+                let mut source = self.clone();
+                source.find_records(rad.into(), fetch, cache_dir, database, pool);
+                source.iaea_records
+            })
+            .find(|records| records.iter().any(|r| r.branching.is_some()))
+            .unwrap_or_default();
+
+        // every decay mode is repeated by each of its records
+        let mut modes: Vec<(String, String, f32)> = Vec::new();
+        for record in &records {
+            let Some(branching) = record.branching else {
+                continue;
+            };
+            let daughter = record.daughter_name();
+            let mode = record.decay_mode.display();
+            if !modes.iter().any(|(d, m, _)| *d == daughter && *m == mode) {
+                modes.push((daughter, mode, branching));
+            }
+        }
+
+        let mut daughters: Vec<(String, f32)> = Vec::new();
+        for (daughter, _, branching) in modes {
+            match daughters.iter_mut().find(|(d, _)| *d == daughter) {
+                Some((_, total)) => *total += branching,
+                None => daughters.push((daughter, branching)),
+            }
+        }
+
+        daughters.retain(|(name, _)| !is_stable(name));
+        daughters
+    }
+
+    /// Compare the records against those fetched from the IAEA right now
+    ///
+    /// Unobserved records are removed from both sets first, so that like is
+    /// compared with like. Every line is paired with the nearest fetched line
+    /// in energy. Returns None if nothing could be fetched.
+    pub fn compare_to_fetched(&self, radtype: iaea::RadType) -> Option<ComparisonResult> {
+        let records = iaea::fetch_nuclide(self.iaea_nuclide.clone(), radtype)?;
+        let mut fetched = Source::from_manual(
+            &self.fispact_name,
+            self.fispact_activity,
+            self.iaea_nuclide.clone(),
+            records,
+        );
+        fetched.remove_unobserved_records();
+
+        let mut local = self.clone();
+        local.remove_unobserved_records();
+
+        let lines = |source: &Source| {
+            source
+                .iaea_records
+                .iter()
+                .filter_map(|r| Some((r.energy?, r.intensity?)))
+                .collect::<Vec<(f32, f32)>>()
         };
+        let fetched_lines = lines(&fetched);
+
+        let mut energy_diff_max_kev = 0.0_f32;
+        let mut intensity_diff_max_pct = 0.0_f32;
+        for (energy, intensity) in lines(&local) {
+            let nearest = fetched_lines
+                .iter()
+                .min_by(|a, b| (a.0 - energy).abs().total_cmp(&(b.0 - energy).abs()));
+
+            if let Some((e, i)) = nearest {
+                energy_diff_max_kev = energy_diff_max_kev.max((e - energy).abs());
+                intensity_diff_max_pct = intensity_diff_max_pct.max((i - intensity).abs());
+            }
+        }
+
+        Some(ComparisonResult {
+            n_local: local.iaea_records.len(),
+            n_fetched: fetched.iaea_records.len(),
+            energy_diff_max_kev,
+            intensity_diff_max_pct,
+        })
+    }
+
+    /// Decay constant [1/s] from the first record with a known half-life
+    pub fn decay_constant_per_s(&self) -> Option<f64> {
+        self.half_life()
+            .map(|half_life| std::f64::consts::LN_2 / half_life as f64)
+    }
+
+    /// Intensity of the strongest record within `tolerance_kev` of an energy
+    pub fn intensity_at_energy(&self, energy_kev: f32, tolerance_kev: f32) -> Option<f32> {
+        self.records_in_range(energy_kev - tolerance_kev, energy_kev + tolerance_kev)
+            .filter_map(|r| r.intensity)
+            .reduce(f32::max)
+    }
+
+    /// Records at or above an energy threshold [keV], leaving `self` untouched
+    pub fn records_above_threshold(&self, threshold_kev: f32) -> impl Iterator<Item = &Record> {
+        self.iaea_records
+            .iter()
+            .filter(move |r| r.energy.unwrap_or(0.0) >= threshold_kev)
+    }
+
+    /// Records with an energy between `min_kev` and `max_kev` inclusive
+    pub fn records_in_range(&self, min_kev: f32, max_kev: f32) -> impl Iterator<Item = &Record> {
+        self.iaea_records
+            .iter()
+            .filter(move |r| r.energy.is_some_and(|e| (min_kev..=max_kev).contains(&e)))
+    }
+
+    /// Pre-fetched records from a user database if given, or the built-in data
+    fn load_records(&self, radtype: iaea::RadType, database: Option<&Path>) -> Option<RecordSet> {
+        database
+            .and_then(|dir| cache::load(dir, &self.iaea_nuclide, radtype))
+            .or_else(|| iaea::load_nuclide(self.iaea_nuclide.clone(), radtype))
+    }
+
+    /// Unique parent energies [keV] in the raw IAEA data, for diagnostics
+    ///
+    /// This bypasses `find_records`, so every isomeric level is included.
+    pub fn parent_energies_available(&self, radtype: iaea::RadType) -> Vec<f32> {
+        iaea::load_nuclide(self.iaea_nuclide.clone(), radtype)
+            .map(|records| unique_parent_energies(&records))
+            .unwrap_or_default()
+    }
+
+    /// Every record for the nuclide, loaded or fetched as requested
+    fn raw_records(
+        &self,
+        radtype: iaea::RadType,
+        fetch: bool,
+        cache_dir: Option<&Path>,
+        database: Option<&Path>,
+        pool: &mut NuclideCache,
+    ) -> Option<RecordSet> {
+        pool.get_or_load(&self.iaea_nuclide, radtype, || match (fetch, cache_dir) {
+            (false, _) => self.load_records(radtype, database),
+            (true, None) => iaea::fetch_nuclide(self.iaea_nuclide.clone(), radtype),
+            (true, Some(dir)) => cache::fetch_with_cache(&self.iaea_nuclide, radtype, dir),
+        })
+    }
+
+    /// Fill records for every isomeric level, without filtering on state
+    ///
+    /// Each record keeps its own parent energy, so the levels can still be
+    /// told apart in the outputs.
+    pub fn find_records_all_states(
+        &mut self,
+        radtype: iaea::RadType,
+        fetch: bool,
+        cache_dir: Option<&Path>,
+        database: Option<&Path>,
+        pool: &mut NuclideCache,
+    ) {
+        self.iaea_records = self
+            .raw_records(radtype, fetch, cache_dir, database, pool)
+            .unwrap_or_default();
+
+        trace!(
+            "{radtype:?} decay records for all states of {}: {} at {:?} keV",
+            self.fispact_name,
+            self.iaea_records.len(),
+            unique_parent_energies(&self.iaea_records)
+        );
+    }
+
+    /// todo: Big mess of edge cases that neads cleaning up
+    pub fn find_records(
+        &mut self,
+        radtype: iaea::RadType,
+        fetch: bool,
+        cache_dir: Option<&Path>,
+        database: Option<&Path>,
+        pool: &mut NuclideCache,
+    ) {
+        let nuclide_records = self.raw_records(radtype, fetch, cache_dir, database, pool);
 
         if nuclide_records.is_none() {
             trace!("{radtype:?} decay records for {}: 0", self.fispact_name,);
@@ -70,12 +533,7 @@ impl Source {
 
         if let Some(records) = nuclide_records {
             // get the list of parent energies
-            let mut parent_energy = records
-                .iter()
-                .filter_map(|r| r.p_energy)
-                .collect::<Vec<f32>>();
-            parent_energy.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
-            parent_energy.dedup();
+            let parent_energy = unique_parent_energies(&records);
 
             // get the index of the parent energy we care about
             let index = if let IsomerState::Excited(i) = self.iaea_nuclide.state {
@@ -93,6 +551,7 @@ impl Source {
                         radtype,
                         self.iaea_nuclide.name_with_state()
                     );
+                    trace!("Available parent energies: {parent_energy:?} keV");
                     return;
                 }
 
@@ -125,6 +584,7 @@ impl Source {
                         radtype,
                         self.iaea_nuclide.name_with_state()
                     );
+                    trace!("Available parent energies: {parent_energy:?} keV");
                     return;
                 }
 
@@ -176,6 +636,62 @@ impl Source {
         }
     }
 
+    /// Merge records with energies within `tolerance_kev` of each other
+    ///
+    /// Lines are merged into a group while within the tolerance of its first
+    /// line, so merges never chain beyond it, and only with lines of the same
+    /// parent level. Intensities of merged records are summed, and the energy
+    /// taken as the intensity-weighted mean. Only valid records should remain,
+    /// so this is expected to follow `remove_unobserved_records`.
+    pub fn dedup_by_energy_proximity(&mut self, tolerance_kev: f32) {
+        if tolerance_kev <= 0.0 {
+            return;
+        }
+
+        let n = self.iaea_records.len();
+        let key = |r: &Record| {
+            (
+                r.p_energy.unwrap_or(f32::NEG_INFINITY),
+                r.energy.unwrap_or(f32::NAN),
+            )
+        };
+        self.iaea_records.sort_by(|a, b| {
+            let ((pa, ea), (pb, eb)) = (key(a), key(b));
+            pa.total_cmp(&pb).then(ea.total_cmp(&eb))
+        });
+
+        let mut merged: RecordSet = Vec::with_capacity(n);
+        let mut first_energy = None;
+        for record in self.iaea_records.drain(..) {
+            if let (Some(last), Some(first)) = (merged.last_mut(), first_energy) {
+                if let (Some(e0), Some(i0), Some(e1), Some(i1)) =
+                    (last.energy, last.intensity, record.energy, record.intensity)
+                {
+                    if last.p_energy == record.p_energy && (e1 - first).abs() <= tolerance_kev {
+                        let total = i0 + i1;
+                        last.energy = match total > 0.0 {
+                            true => Some((e0 * i0 + e1 * i1) / total),
+                            false => Some(0.5 * (e0 + e1)),
+                        };
+                        last.intensity = Some(total);
+                        continue;
+                    }
+                }
+            }
+            first_energy = record.energy;
+            merged.push(record);
+        }
+        self.iaea_records = merged;
+
+        if n != self.iaea_records.len() {
+            debug!(
+                "Merged {} {} records within {tolerance_kev} keV",
+                n - self.iaea_records.len(),
+                self.fispact_name
+            );
+        }
+    }
+
     /// Sort records
     pub fn sort_records(&mut self, property: &SortProperty) {
         match property {
@@ -193,4 +709,400 @@ impl Source {
             }
         }
     }
+
+    /// Keep only records from any of the given decay modes
+    pub fn retain_decay_modes(&mut self, modes: &[String]) {
+        self.iaea_records
+            .retain(|r| modes.iter().any(|m| matches_decay_mode(r, m)));
+    }
+
+    /// Unique decay modes present in the records
+    pub fn decay_modes(&self) -> Vec<String> {
+        let mut modes = self
+            .records_by_decay_mode()
+            .into_keys()
+            .collect::<Vec<String>>();
+        modes.sort();
+        modes
+    }
+
+    /// Records grouped by the name of their decay mode
+    pub fn records_by_decay_mode(&self) -> HashMap<String, Vec<&Record>> {
+        self.iaea_records.by_decay_mode()
+    }
+
+    /// Keep only the highest energy record, carrying the total intensity
+    ///
+    /// For beta decay this reduces the nuclide to a single line at the
+    /// maximum endpoint energy, with the same number of particles per decay.
+    pub fn keep_endpoint_only(&mut self) {
+        let total = self
+            .iaea_records
+            .iter()
+            .filter_map(|r| r.intensity)
+            .sum::<f32>();
+        let Some(mut endpoint) = self
+            .iaea_records
+            .iter()
+            .filter(|r| r.energy.is_some())
+            .max_by(|a, b| a.energy.partial_cmp(&b.energy).unwrap())
+            .cloned()
+        else {
+            return;
+        };
+
+        endpoint.intensity = Some(total);
+        self.iaea_records = vec![endpoint];
+    }
+
+    /// Limit the number of records to the first `max` entries
+    pub fn truncate_records(&mut self, max: usize) {
+        let n = self.iaea_records.len();
+        if n > max {
+            warn!("{}: truncated from {n} to {max} records", self.fispact_name);
+            self.iaea_records.truncate(max);
+        }
+    }
+}
+
+/// Compare sources by activity, then by name for equal activities
+///
+/// The order is total, with NaN activities above everything else, so may be
+/// used directly with `sort_by` or `max_by`.
+pub fn cmp_by_activity(a: &Source, b: &Source) -> std::cmp::Ordering {
+    a.fispact_activity
+        .total_cmp(&b.fispact_activity)
+        .then_with(|| a.fispact_name.cmp(&b.fispact_name))
+        .then_with(|| {
+            a.iaea_nuclide
+                .name_with_state()
+                .cmp(&b.iaea_nuclide.name_with_state())
+        })
+}
+
+/// Order nuclides by the chosen property
+pub fn sort_sources(sources: &mut [Source], property: NuclideSort) {
+    // nuclides without a known half-life always go last
+    let half_life = |s: &Source, missing: f32| s.half_life().unwrap_or(missing);
+
+    match property {
+        NuclideSort::Name => sources.sort_by_key(|s| s.fispact_name.clone()),
+        NuclideSort::Activity => {
+            sources.sort_by(|a, b| b.fispact_activity.total_cmp(&a.fispact_activity))
+        }
+        NuclideSort::PhotonStrength => sources.sort_by(|a, b| {
+            b.photon_yield_per_second()
+                .total_cmp(&a.photon_yield_per_second())
+        }),
+        NuclideSort::HalflifeAsc => sources
+            .sort_by(|a, b| half_life(a, f32::INFINITY).total_cmp(&half_life(b, f32::INFINITY))),
+        NuclideSort::HalflifeDesc => sources.sort_by(|a, b| {
+            half_life(b, f32::NEG_INFINITY).total_cmp(&half_life(a, f32::NEG_INFINITY))
+        }),
+    }
+}
+
+/// Differences between the records of a source and a fresh IAEA fetch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComparisonResult {
+    /// Number of records held by the source
+    pub n_local: usize,
+    /// Number of records fetched from the IAEA
+    pub n_fetched: usize,
+    /// Largest difference between a line and the nearest fetched line [keV]
+    pub energy_diff_max_kev: f32,
+    /// Largest difference in intensity between those lines [%]
+    pub intensity_diff_max_pct: f32,
+}
+
+/// Summary statistics of the line energies in a set of records
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordStats {
+    /// Intensity-weighted mean energy [keV]
+    pub mean_kev: f32,
+    /// Energy at which the cumulative intensity reaches half the total [keV]
+    pub median_kev: f32,
+    /// Energy of the most intense line [keV]
+    pub mode_kev: f32,
+    /// Sum of all intensities [%]
+    pub total_intensity: f32,
+    /// Number of records with both an energy and intensity
+    pub n_records: usize,
+}
+
+/// Energy statistics of the records, or None without any valid lines
+///
+/// With two equally intense lines, the median is the lower of the two
+/// energies since the cumulative intensity reaches half the total there.
+pub fn record_set_stats(records: &RecordSet) -> Option<RecordStats> {
+    line_stats(
+        records
+            .iter()
+            .filter_map(|r| Some((r.energy?, r.intensity?)))
+            .collect(),
+    )
+}
+
+/// Energy statistics of (energy [keV], intensity [%]) lines
+fn line_stats(mut lines: Vec<(f32, f32)>) -> Option<RecordStats> {
+    let total_intensity = lines.iter().map(|(_, i)| i).sum::<f32>();
+    if lines.is_empty() || total_intensity <= 0.0 {
+        return None;
+    }
+
+    lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mean_kev = lines.iter().map(|(e, i)| e * i).sum::<f32>() / total_intensity;
+
+    let mut cumulative = 0.0;
+    let median_kev = lines
+        .iter()
+        .find(|(_, i)| {
+            cumulative += i;
+            cumulative >= 0.5 * total_intensity
+        })
+        .map_or(lines[lines.len() - 1].0, |(e, _)| *e);
+
+    let mode_kev = lines
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(e, _)| *e)?;
+
+    Some(RecordStats {
+        mean_kev,
+        median_kev,
+        mode_kev,
+        total_intensity,
+        n_records: lines.len(),
+    })
+}
+
+/// Half-life [s] beyond which a nuclide is treated as stable
+const STABLE_HALF_LIFE: f32 = 1.0e18;
+
+/// Whether a nuclide has no decay data or an effectively infinite half-life
+///
+/// Only the built-in data are checked, so this never fetches from the IAEA.
+fn is_stable(name: &str) -> bool {
+    let Ok(nuclide) = iaea::Nuclide::try_from(name.to_string()) else {
+        trace!("Unable to check stability of unrecognised daughter {name}");
+        return false;
+    };
+
+    // isomeric states always decay to something
+    if matches!(nuclide.state, IsomerState::Excited(_)) {
+        return false;
+    }
+
+    let half_life = CliRadType::value_variants()
+        .iter()
+        .filter_map(|rad| iaea::load_nuclide(nuclide.clone(), (*rad).into()))
+        .flatten()
+        .filter(|r| r.p_energy.unwrap_or(0.0) == 0.0)
+        .find_map(|r| r.half_life);
+
+    half_life.map_or(true, |t| t > STABLE_HALF_LIFE)
+}
+
+/// Decay record for a manually defined line
+///
+/// Only the energy [keV] and intensity [%] are known, so everything else,
+/// such as the parent level, decay mode, and half-life, is left unset.
+pub fn manual_record(energy_kev: f32, intensity: f32) -> Record {
+    Record {
+        energy: Some(energy_kev),
+        intensity: Some(intensity),
+        ..Default::default()
+    }
+}
+
+/// Sorted and deduplicated parent energies [keV] of a set of records
+fn unique_parent_energies(records: &RecordSet) -> Vec<f32> {
+    let mut energies = records
+        .iter()
+        .filter_map(|r| r.p_energy)
+        .collect::<Vec<f32>>();
+    energies.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    energies.dedup();
+    energies
+}
+
+/// Case-insensitive check of a record decay mode, e.g. "b-", "IT", or "ec"
+pub fn matches_decay_mode(record: &Record, filter: &str) -> bool {
+    record
+        .decay_mode
+        .display()
+        .eq_ignore_ascii_case(filter.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(name: &str, activity: f64) -> Source {
+        let nuclide = iaea::Nuclide::try_from(name.to_string()).unwrap();
+        Source::from_manual(name, activity, nuclide, Vec::new())
+    }
+
+    /// Source with the built-in records of a radiation type
+    fn with_records(name: &str, rad: CliRadType) -> Source {
+        let mut s = source(name, 1.0);
+        s.find_records(rad.into(), false, None, None, &mut NuclideCache::default());
+        assert!(!s.iaea_records.is_empty(), "no {rad} records for {name}");
+        s
+    }
+
+    #[test]
+    fn co60_decay_constant() {
+        let lambda = with_records("Co60", CliRadType::Gamma)
+            .decay_constant_per_s()
+            .unwrap();
+        assert!((lambda - 4.17e-9).abs() < 0.01e-9, "{lambda}");
+    }
+
+    #[test]
+    fn co60_norm_uncertainty() {
+        // both strong lines are known to better than 0.1%
+        let uncertainty = with_records("Co60", CliRadType::Gamma).norm_uncertainty();
+        assert!(
+            uncertainty.is_some_and(|u| u > 0.0 && u < 1e-2),
+            "{uncertainty:?}"
+        );
+        assert_eq!(source("Co60", 1.0).norm_uncertainty(), None);
+    }
+
+    #[test]
+    fn no_decay_constant_without_records() {
+        assert_eq!(source("Co60", 1.0).decay_constant_per_s(), None);
+    }
+
+    #[test]
+    fn bimodal_stats() {
+        let stats = line_stats(vec![(200.0, 50.0), (100.0, 50.0)]).unwrap();
+        assert_eq!(stats.mean_kev, 150.0);
+        assert_eq!(stats.median_kev, 100.0);
+        assert_eq!(stats.total_intensity, 100.0);
+        assert_eq!(stats.n_records, 2);
+    }
+
+    #[test]
+    fn stats_mode_and_median() {
+        let stats = line_stats(vec![(100.0, 10.0), (500.0, 60.0), (900.0, 30.0)]).unwrap();
+        assert_eq!(stats.mode_kev, 500.0);
+        assert_eq!(stats.median_kev, 500.0);
+    }
+
+    #[test]
+    fn no_stats_without_lines() {
+        assert_eq!(line_stats(Vec::new()), None);
+        assert_eq!(line_stats(vec![(100.0, 0.0)]), None);
+    }
+
+    #[test]
+    fn cs137_records_by_decay_mode() {
+        // every Cs137 line follows beta-minus decay, so there is one group
+        let cs137 = with_records("Cs137", CliRadType::Gamma);
+        let modes = cs137.records_by_decay_mode();
+        assert_eq!(modes.len(), 1);
+        assert_eq!(
+            modes.values().next().unwrap().len(),
+            cs137.iaea_records.len()
+        );
+    }
+
+    #[test]
+    fn eu152_records_by_decay_mode() {
+        // Eu152 decays by both electron capture and beta-minus
+        let eu152 = with_records("Eu152", CliRadType::Gamma);
+        let modes = eu152.records_by_decay_mode();
+        assert!(modes.len() >= 2, "{:?}", modes.keys());
+
+        let n_grouped = modes.values().map(Vec::len).sum::<usize>();
+        assert_eq!(n_grouped, eu152.iaea_records.len());
+        for (mode, records) in &modes {
+            assert!(records.iter().all(|r| r.decay_mode.display() == *mode));
+        }
+    }
+
+    /// Source with a line at each (parent level, energy, intensity)
+    fn with_lines(lines: &[(f32, f32, f32)]) -> Source {
+        let mut source = source("Co60", 1.0);
+        for (p_energy, energy, intensity) in lines {
+            let mut record = manual_record(*energy, *intensity);
+            record.p_energy = Some(*p_energy);
+            source.iaea_records.push(record);
+        }
+        source
+    }
+
+    #[test]
+    fn merges_do_not_chain() {
+        let mut source = with_lines(&[(0.0, 100.0, 1.0), (0.0, 100.8, 1.0), (0.0, 101.6, 1.0)]);
+        source.dedup_by_energy_proximity(1.0);
+
+        let energies = source
+            .iaea_records
+            .iter()
+            .filter_map(|r| r.energy)
+            .collect::<Vec<f32>>();
+        assert_eq!(energies, [100.4, 101.6]);
+        assert_eq!(source.iaea_records[0].intensity, Some(2.0));
+    }
+
+    #[test]
+    fn merges_only_within_a_parent_level() {
+        let mut source = with_lines(&[(80.0, 100.0, 1.0), (0.0, 100.5, 1.0), (0.0, 100.2, 1.0)]);
+        source.dedup_by_energy_proximity(1.0);
+
+        assert_eq!(source.iaea_records.len(), 2);
+        assert_eq!(source.iaea_records[0].p_energy, Some(0.0));
+        assert_eq!(source.iaea_records[0].intensity, Some(2.0));
+        assert_eq!(source.iaea_records[1].p_energy, Some(80.0));
+    }
+
+    #[test]
+    fn nan_energies_do_not_panic() {
+        let mut source = with_lines(&[(0.0, f32::NAN, 1.0), (0.0, 100.0, 1.0)]);
+        source.dedup_by_energy_proximity(1.0);
+        assert_eq!(source.iaea_records.len(), 2);
+    }
+
+    #[test]
+    fn max_is_most_active() {
+        let sources = [
+            source("Co60", 1.0e3),
+            source("Cs137", 5.0e6),
+            source("Mn54", 2.0e4),
+        ];
+        let most_active = sources.iter().max_by(|a, b| cmp_by_activity(a, b));
+        assert_eq!(most_active.unwrap().fispact_name, "Cs137");
+    }
+
+    #[test]
+    fn equal_activity_is_ordered_by_name() {
+        let co60 = source("Co60", 1.0e3);
+        let mn54 = source("Mn54", 1.0e3);
+        assert_eq!(cmp_by_activity(&co60, &mn54), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn equality_ignores_activity() {
+        assert_eq!(source("Co60", 1.0e3), source("Co60", 5.0e6));
+        assert_ne!(source("Co60", 1.0e3), source("Mn54", 1.0e3));
+    }
+
+    #[test]
+    fn nan_activity_has_a_total_order() {
+        let mut sources = vec![
+            source("Co60", 1.0e3),
+            source("Mn54", f64::NAN),
+            source("Cs137", 5.0e6),
+        ];
+        sources.sort_by(|a, b| cmp_by_activity(b, a));
+        let names = sources
+            .iter()
+            .map(|s| s.fispact_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Mn54", "Cs137", "Co60"]);
+    }
 }
@@ -0,0 +1,134 @@
+// internal
+use crate::fileio::{open_output, ExistsAction};
+use crate::source::Source;
+
+// neutronics toolbox
+use ntools::utils::{f, ValueExt};
+
+// standard lib
+use std::io::Write;
+use std::path::Path;
+
+// external
+use anyhow::Result;
+
+/// Aggregate all sources into an activity-weighted energy histogram
+///
+/// Each record contributes `fispact_activity * intensity` to the bin containing
+/// its energy. Returns the bin centre [keV] and relative intensity of each bin,
+/// normalised to a total of 1.
+pub fn build_spectrum(sources: &[Source], n_bins: usize) -> Vec<(f32, f32)> {
+    let Some((lower, width)) = binning(sources, n_bins) else {
+        return Vec::new();
+    };
+
+    let mut weights = vec![0.0_f64; n_bins];
+    for s in sources {
+        for r in &s.iaea_records {
+            if let (Some(e), Some(i)) = (r.energy, r.intensity) {
+                let bin = (((e - lower) / width) as usize).min(n_bins - 1);
+                weights[bin] += s.fispact_activity * i as f64;
+            }
+        }
+    }
+
+    let total = weights.iter().sum::<f64>();
+    weights
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let centre = lower + (i as f32 + 0.5) * width;
+            let relative = if total > 0.0 { w / total } else { 0.0 };
+            (centre, relative as f32)
+        })
+        .collect()
+}
+
+/// Emission rate of every unique line energy summed over all sources
+///
+/// Returns the energy [keV] and `sum(fispact_activity * intensity / 100)` over
+/// every nuclide with a line at that energy, sorted by energy.
+pub fn aggregate_spectrum(sources: &[Source]) -> Vec<(f32, f64)> {
+    let mut lines = sources
+        .iter()
+        .flat_map(|s| {
+            s.iaea_records.iter().filter_map(|r| {
+                let (e, i) = (r.energy?, r.intensity?);
+                Some((e, s.fispact_activity * i as f64 * 1e-2))
+            })
+        })
+        .collect::<Vec<(f32, f64)>>();
+
+    // combine the rates of lines shared between nuclides
+    lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+    lines.dedup_by(|later, earlier| {
+        let duplicate = later.0 == earlier.0;
+        if duplicate {
+            earlier.1 += later.1;
+        }
+        duplicate
+    });
+
+    lines
+}
+
+/// Lower bound and uniform bin width [keV] covering every record energy
+pub fn binning(sources: &[Source], n_bins: usize) -> Option<(f32, f32)> {
+    if n_bins == 0 {
+        return None;
+    }
+
+    let energies = sources
+        .iter()
+        .flat_map(|s| s.iaea_records.iter().filter_map(|r| r.energy))
+        .collect::<Vec<f32>>();
+
+    let e_min = energies.iter().copied().reduce(f32::min)?;
+    let e_max = energies.iter().copied().reduce(f32::max)?;
+
+    // a single line still needs a finite bin to sit in
+    if e_max <= e_min {
+        return Some((e_min - 0.5, 1.0 / n_bins as f32));
+    }
+
+    Some((e_min, (e_max - e_min) / n_bins as f32))
+}
+
+/// Writes the spectrum as a two column text file
+pub fn write(
+    spectrum: &[(f32, f32)],
+    path: &Path,
+    index: usize,
+    compress: bool,
+    exists: ExistsAction,
+) -> Result<()> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("step");
+    let path = path.with_file_name(f!("{name}_spectrum"));
+
+    let Some(mut f) = open_output(
+        &path,
+        "txt",
+        &f!("step_{index}_spectrum.txt"),
+        compress,
+        exists,
+    )?
+    else {
+        return Ok(());
+    };
+    write_to_writer(spectrum, &mut f)?;
+    f.finish()
+}
+
+/// Writes the spectrum as a two column table to any writer
+pub fn write_to_writer<W: Write>(spectrum: &[(f32, f32)], mut writer: W) -> Result<()> {
+    let mut text = String::from("# Energy [keV]   Relative intensity\n");
+    for (energy, intensity) in spectrum {
+        text += &f!("  {}      {}\n", energy.sci(5, 2), intensity.sci(5, 2));
+    }
+
+    writer.write_all(text.as_bytes())?;
+    Ok(())
+}
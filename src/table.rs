@@ -1,6 +1,7 @@
 // internal
-use crate::create_file_with_fallback;
-use crate::source::Source;
+use crate::cli::{Cli, NuclideSort, TextWidth};
+use crate::fileio::{open_output, ExistsAction};
+use crate::source::{sort_sources, Source};
 
 // standard lib
 use std::io::Write;
@@ -14,54 +15,325 @@ use ntools::utils::{OptionExt, ValueExt};
 use anyhow::Result;
 use log::warn;
 
+/// Options for formatting the text table
+#[derive(Debug, Clone)]
+pub struct TableConfig {
+    /// Decimal places for branching ratios
+    pub branching_precision: usize,
+    /// Show intensities relative to the strongest line of each nuclide
+    pub relative_intensity: bool,
+    /// Show the uncertainty on each intensity
+    pub show_uncertainty: bool,
+    /// Separate columns with a delimiter rather than aligning with spaces
+    pub delimiter: Option<char>,
+    /// Replace any non-ASCII characters
+    pub ascii: bool,
+    /// Fit the column widths to the content
+    pub width: TextWidth,
+    /// List the stable daughters under each nuclide header
+    pub stable_daughters: bool,
+    /// Number each record by its position in the nuclide
+    pub record_index: bool,
+    /// Order of the nuclides, if different to the other outputs
+    pub sort: Option<NuclideSort>,
+}
+
+/// Width of the record index column, e.g. "[12] "
+const INDEX_WIDTH: usize = 6;
+
+impl TableConfig {
+    /// Width of the branching ratio column for the chosen precision
+    fn branching_width(&self) -> usize {
+        self.branching_precision + 8
+    }
+}
+
+impl From<&Cli> for TableConfig {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            branching_precision: cli.branching_precision,
+            relative_intensity: cli.relative_intensity,
+            show_uncertainty: cli.show_uncertainty,
+            delimiter: cli.text_delimiter,
+            ascii: cli.text_no_unicode,
+            width: cli.text_width,
+            stable_daughters: cli.show_stable_daughters,
+            record_index: cli.text_show_record_index,
+            sort: cli.text_sort_nuclides,
+        }
+    }
+}
+
+/// Widths of the text table columns
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnWidths {
+    /// Parent nuclide name
+    pub name: usize,
+    /// Decay mode
+    pub mode: usize,
+    /// Daughter nuclide name
+    pub daughter: usize,
+    /// Line energy
+    pub energy: usize,
+    /// Line intensity
+    pub intensity: usize,
+}
+
+impl Default for ColumnWidths {
+    fn default() -> Self {
+        Self {
+            name: 5,
+            mode: 5,
+            daughter: 5,
+            energy: 7,
+            intensity: 7,
+        }
+    }
+}
+
+/// Widest entry of every column, with the default widths as a minimum
+pub fn compute_column_widths(nuclides: &[Source]) -> ColumnWidths {
+    let mut widths = ColumnWidths::default();
+
+    for record in nuclides.iter().flat_map(|n| &n.iaea_records) {
+        widths.name = widths.name.max(record.parent_name().len());
+        widths.mode = widths.mode.max(record.decay_mode.display().len());
+        widths.daughter = widths.daughter.max(record.daughter_name().len());
+        widths.energy = widths.energy.max(format_energy(record.energy).len());
+        widths.intensity = widths
+            .intensity
+            .max(format_intensity(record.intensity).len());
+    }
+
+    widths
+}
+
 /// Represents a complete table of decay data for nuclides.
 pub struct Table(String);
 
 impl Table {
     /// Creates a new `Table` from a slice of `Source`.
-    pub fn new(nuclides: &[Source]) -> Self {
-        let mut s = header();
-        s += &content(nuclides);
-        Self(s)
+    pub fn new(nuclides: &[Source], config: &TableConfig) -> Self {
+        let mut sorted = nuclides.to_vec();
+        if let Some(property) = config.sort {
+            sort_sources(&mut sorted, property);
+        }
+        let nuclides = sorted.as_slice();
+
+        let widths = match config.width {
+            TextWidth::Auto => compute_column_widths(nuclides),
+            TextWidth::Fixed => ColumnWidths::default(),
+        };
+
+        let mut s = header(config, &widths);
+        s += &content(nuclides, config, &widths);
+        match config.ascii {
+            true => Self(ascii_safe(&s)),
+            false => Self(s),
+        }
+    }
+
+    /// Side-by-side comparison of the nuclides of two intervals
+    ///
+    /// Rows are the union of nuclides in both, with activities, their ratio
+    /// B/A, and the number of lines. Nuclides missing from B are marked
+    /// `(dropped)`, and those missing from A are marked `(new)`.
+    pub fn from_interval_comparison(
+        sources_a: &[Source],
+        sources_b: &[Source],
+        label_a: &str,
+        label_b: &str,
+    ) -> Self {
+        let mut names = sources_a
+            .iter()
+            .chain(sources_b)
+            .map(|s| s.fispact_name.clone())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+
+        let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(7);
+        let activity_a = format!("activity {label_a}");
+        let activity_b = format!("activity {label_b}");
+        let lines_a = format!("lines {label_a}");
+        let lines_b = format!("lines {label_b}");
+        let col = [&activity_a, &activity_b, &lines_a, &lines_b]
+            .iter()
+            .map(|c| c.len())
+            .max()
+            .unwrap_or(0)
+            .max(11);
+
+        let heading = format!(
+            " {:<width$}  {:>col$}  {:>col$}  {:>11}  {:>col$}  {:>col$}",
+            "Nuclide", activity_a, activity_b, "ratio", lines_a, lines_b
+        );
+        let rule = format!(" {:-<1$}\n", "", heading.len() - 1);
+        let mut s = format!("{rule}{heading}\n{rule}");
+
+        for name in names {
+            let a = sources_a.iter().find(|s| s.fispact_name == name);
+            let b = sources_b.iter().find(|s| s.fispact_name == name);
+
+            let (act_a, act_b, ratio, n_a, n_b) = match (a, b) {
+                (Some(a), Some(b)) => (
+                    a.fispact_activity.sci(5, 2),
+                    b.fispact_activity.sci(5, 2),
+                    match a.fispact_activity > 0.0 {
+                        true => (b.fispact_activity / a.fispact_activity).sci(5, 2),
+                        false => "-".to_string(),
+                    },
+                    a.iaea_records.len().to_string(),
+                    b.iaea_records.len().to_string(),
+                ),
+                (Some(a), None) => (
+                    a.fispact_activity.sci(5, 2),
+                    "(dropped)".to_string(),
+                    "-".to_string(),
+                    a.iaea_records.len().to_string(),
+                    "(dropped)".to_string(),
+                ),
+                (None, Some(b)) => (
+                    "(new)".to_string(),
+                    b.fispact_activity.sci(5, 2),
+                    "-".to_string(),
+                    "(new)".to_string(),
+                    b.iaea_records.len().to_string(),
+                ),
+                (None, None) => continue,
+            };
+
+            s += &format!(
+                " {name:<width$}  {act_a:>col$}  {act_b:>col$}  {ratio:>11}  {n_a:>col$}  {n_b:>col$}\n"
+            );
+        }
+
+        Self(s + &rule)
     }
 
     /// Prints the table to the standard output.
-    #[allow(dead_code)]
     pub fn print(&self) {
-        println!("{}", self.0)
+        println!("{self}")
     }
 
     /// Writes the table to a file at the specified path.
-    pub fn write(&self, path: &Path) -> Result<()> {
-        let mut f = create_file_with_fallback(path, "txt", "table.txt")?;
-        f.write_all(self.0.as_bytes())?;
+    pub fn write(&self, path: &Path, compress: bool, exists: ExistsAction) -> Result<()> {
+        let Some(mut f) = open_output(path, "txt", "table.txt", compress, exists)? else {
+            return Ok(());
+        };
+        self.write_to_writer(&mut f)?;
+        f.finish()
+    }
+
+    /// Writes the table to any writer, e.g. a file or in-memory buffer.
+    pub fn write_to_writer<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(self.0.as_bytes())?;
         Ok(())
     }
 }
 
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// Generates the table header.
-fn header() -> String {
+fn header(config: &TableConfig, widths: &ColumnWidths) -> String {
+    if let Some(d) = config.delimiter {
+        let mut columns = vec![];
+        if config.record_index {
+            columns.push("Index");
+        }
+        columns.extend([
+            "Nuclide",
+            "Parent energy [keV]",
+            "Half-life [s]",
+            "P",
+            "Mode",
+            "D",
+            "BR [%]",
+            "Energy [keV]",
+            match config.relative_intensity {
+                true => "Relative intensity [%]",
+                false => "Intensity [%]",
+            },
+        ]);
+        if config.show_uncertainty {
+            columns.push("+/- [%]");
+        }
+        return columns.join(&d.to_string()) + "\n";
+    }
+
+    let br_width = config.branching_width();
+    let ColumnWidths {
+        name,
+        mode,
+        daughter,
+        energy,
+        intensity,
+    } = *widths;
+
+    // energy and intensity headers are wider than the default columns
+    let energy_pad = " ".repeat(energy - 7);
+    let intensity_pad = " ".repeat(intensity - 7);
+
+    let mut width = br_width + name + mode + daughter + energy + intensity + 24;
+    if config.show_uncertainty {
+        width += 10;
+    }
+
+    let index_pad = match config.record_index {
+        true => {
+            width += INDEX_WIDTH;
+            " ".repeat(INDEX_WIDTH)
+        }
+        false => String::new(),
+    };
+
     let mut table = String::new();
-    table.push_str(&format!("{:-<58}\n", ""));
+    table.push_str(&format!("{:-<width$}\n", ""));
     table.push_str(&format!(
-        "  {:^5}   {:^5}  {:^5}   BR    Energy [keV]  Intensity [%]\n",
-        "P", "Mode", "D"
+        "{index_pad}  {:^name$}   {:^mode$}  {:^daughter$}  {:^br_width$}  Energy [keV]{energy_pad}  Intensity [%]{}\n",
+        "P",
+        "Mode",
+        "D",
+        "BR",
+        if config.show_uncertainty {
+            format!("{intensity_pad}     +/- [%]")
+        } else {
+            String::new()
+        }
     ));
-    table.push_str(&format!("{:-<58}\n", ""));
+    if config.relative_intensity {
+        table.push_str(&format!(
+            "{:>width$}\n",
+            "(relative to strongest line = 100%)"
+        ));
+    }
+    table.push_str(&format!("{:-<width$}\n", ""));
     table
 }
 
 /// Generates the table content for all nuclide records.
-fn content(nuclides: &[Source]) -> String {
+fn content(nuclides: &[Source], config: &TableConfig, widths: &ColumnWidths) -> String {
     let mut table = String::new();
     let mut missing_p_erg = false;
 
     for nuclide in nuclides {
         let mut p_energy = -1.0;
-        table += &format_nuclide_header(nuclide, &mut p_energy, &mut missing_p_erg);
+        table += &format_nuclide_header(nuclide, &mut p_energy, &mut missing_p_erg, config);
 
-        for record in &nuclide.iaea_records {
-            table += &format_record(nuclide, record, &mut p_energy, &mut missing_p_erg);
+        for (index, record) in nuclide.iaea_records.iter().enumerate() {
+            table += &format_record(
+                nuclide,
+                record,
+                config.record_index.then_some(index),
+                &mut p_energy,
+                &mut missing_p_erg,
+                config,
+                widths,
+            );
         }
 
         missing_p_erg = false;
@@ -71,9 +343,19 @@ fn content(nuclides: &[Source]) -> String {
 }
 
 /// Formats the header for a single nuclide.
-fn format_nuclide_header(nuclide: &Source, p_energy: &mut f32, missing_p_erg: &mut bool) -> String {
+fn format_nuclide_header(
+    nuclide: &Source,
+    p_energy: &mut f32,
+    missing_p_erg: &mut bool,
+    config: &TableConfig,
+) -> String {
     let mut header = String::new();
 
+    // every delimited row already names the nuclide
+    if config.delimiter.is_some() {
+        return header;
+    }
+
     for record in &nuclide.iaea_records {
         let parent_energy = record.p_energy.unwrap_or_else(|| {
             if !*missing_p_erg {
@@ -88,8 +370,16 @@ fn format_nuclide_header(nuclide: &Source, p_energy: &mut f32, missing_p_erg: &m
 
         if parent_energy > *p_energy {
             *p_energy = parent_energy;
+            let prefix = match nuclide.is_isomeric() {
+                true => "[ISOMER] ",
+                false => "",
+            };
+            let chain = match nuclide.from_chain {
+                true => " (computed from chain)",
+                false => "",
+            };
             header += &format!(
-                "\n {} [E = {parent_energy} keV, t1/2 = {}]\n",
+                "\n {prefix}{} [E = {parent_energy} keV, t1/2 = {}]{chain}\n",
                 nuclide.fispact_name,
                 human_readable_halflife(record.half_life),
             )
@@ -97,6 +387,13 @@ fn format_nuclide_header(nuclide: &Source, p_energy: &mut f32, missing_p_erg: &m
         }
     }
 
+    if config.stable_daughters {
+        let daughters = nuclide.stable_daughters();
+        if !daughters.is_empty() {
+            header += &format!(" Stable daughters: {}\n", daughters.join(", "));
+        }
+    }
+
     header
 }
 
@@ -104,10 +401,21 @@ fn format_nuclide_header(nuclide: &Source, p_energy: &mut f32, missing_p_erg: &m
 fn format_record(
     nuclide: &Source,
     record: &Record,
+    index: Option<usize>,
     p_energy: &mut f32,
     missing_p_erg: &mut bool,
+    config: &TableConfig,
+    widths: &ColumnWidths,
 ) -> String {
     let mut record_str = String::new();
+    let br_width = config.branching_width();
+    let ColumnWidths {
+        name,
+        mode,
+        daughter,
+        energy,
+        intensity,
+    } = *widths;
 
     let parent_energy = record.p_energy.unwrap_or_else(|| {
         if !*missing_p_erg {
@@ -120,37 +428,82 @@ fn format_record(
         0.0
     });
 
+    let intensity = match config.relative_intensity {
+        true => record
+            .intensity
+            .zip(nuclide.dominant_intensity().filter(|d| *d > 0.0))
+            .map(|(i, dominant)| 100.0 * i / dominant),
+        false => record.intensity,
+    };
+
+    if let Some(d) = config.delimiter {
+        let mut columns = index.map(|i| vec![i.to_string()]).unwrap_or_default();
+        columns.extend([
+            nuclide.fispact_name.clone(),
+            parent_energy.to_string(),
+            record.half_life.display(),
+            record.parent_name(),
+            record.decay_mode.display(),
+            record.daughter_name(),
+            record.branching.display(),
+            record.energy.display(),
+            intensity.display(),
+        ]);
+        if config.show_uncertainty {
+            columns.push(record.unc_i.display());
+        }
+        return columns.join(&d.to_string()) + "\n";
+    }
+
     if parent_energy > *p_energy {
         *p_energy = parent_energy;
         record_str += "\n";
     }
 
+    if let Some(i) = index {
+        record_str += &format!("{:<INDEX_WIDTH$}", format!("[{i}]"));
+    }
+
     record_str += &format!(
-        "  {:<5} > {:^5} > {:<5} {:<6}     {:<7}     {:<7}\n",
+        "  {:<name$} > {:^mode$} > {:<daughter$} {:<br_width$}     {:<energy$}     {:<intensity$}",
         record.parent_name(),
         record.decay_mode.display(),
         record.daughter_name(),
-        format_branching(record.branching),
+        format_branching(record.branching, config.branching_precision),
         format_energy(record.energy),
-        format_intensity(record.intensity)
+        format_intensity(intensity)
     )
     .to_string();
 
+    if config.show_uncertainty {
+        record_str += &format!("     {:<intensity$}", format_intensity(record.unc_i));
+    }
+    record_str += "\n";
+
     record_str
 }
 
-/// Formats the branching ratio.
-fn format_branching(branching: Option<f32>) -> String {
+/// Formats the branching ratio to the given number of decimal places.
+///
+/// Ratios of 99.9% or more are left blank, and those below 1% are written in
+/// scientific notation. Anything that would round up to 1% is written as such
+/// rather than as "1.0e0%".
+pub fn format_branching(branching: Option<f32>, precision: usize) -> String {
+    let one_percent = 1.0 - 0.05 * 10f32.powi(-(precision as i32));
+
     match branching {
-        Some(br) if br >= 100.0 => String::new(),
-        Some(br) if br >= 1.0 => format!("({:.0}%)", br),
-        Some(_) => "(< 1%)".to_string(),
+        Some(br) if br >= 99.9 => String::new(),
+        Some(br) if br >= one_percent => format!("({:.precision$}%)", br),
+        Some(br) => format!("({:.precision$e}%)", br),
         None => "None".to_string(),
     }
 }
 
 /// Formats the energy value.
-fn format_energy(energy: Option<f32>) -> String {
+///
+/// Two decimal places from 10 keV, three from 0.001 keV, and scientific
+/// notation below that.
+pub fn format_energy(energy: Option<f32>) -> String {
     match energy {
         Some(e) if e >= 10.0 => format!("{:.2}", e),
         Some(e) if e >= 0.001 => format!("{:.3}", e),
@@ -160,7 +513,10 @@ fn format_energy(energy: Option<f32>) -> String {
 }
 
 /// Formats the intensity value.
-fn format_intensity(intensity: Option<f32>) -> String {
+///
+/// One decimal place from 100%, two from 10%, three from 0.001%, and
+/// scientific notation below that.
+pub fn format_intensity(intensity: Option<f32>) -> String {
     match intensity {
         Some(i) if i >= 100.0 => format!("{:.1}", i),
         Some(i) if i >= 10.0 => format!("{:.2}", i),
@@ -171,7 +527,7 @@ fn format_intensity(intensity: Option<f32>) -> String {
 }
 
 /// Converts an optional half-life value in seconds to a human-readable string.
-fn human_readable_halflife(halflife: Option<f32>) -> String {
+pub fn human_readable_halflife(halflife: Option<f32>) -> String {
     if let Some(seconds) = halflife {
         const SECONDS_IN_MINUTE: f32 = 60.0;
         const SECONDS_IN_HOUR: f32 = 60.0 * SECONDS_IN_MINUTE;
@@ -198,3 +554,86 @@ fn human_readable_halflife(halflife: Option<f32>) -> String {
         "-".to_string()
     }
 }
+
+/// Replace common symbols with ASCII equivalents, and anything else with '?'
+pub fn ascii_safe(s: &str) -> String {
+    const SUBSTITUTIONS: [(char, &str); 11] = [
+        ('\u{03bc}', "u"),
+        ('\u{00b5}', "u"),
+        ('\u{00b1}', "+/-"),
+        ('\u{00d7}', "x"),
+        ('\u{00b0}', "deg"),
+        ('\u{00bd}', "1/2"),
+        ('\u{03b1}', "a"),
+        ('\u{03b2}', "b"),
+        ('\u{03b3}', "g"),
+        ('\u{2212}', "-"),
+        ('\u{00b7}', "*"),
+    ];
+
+    s.chars()
+        .map(|c| match c.is_ascii() {
+            true => c.to_string(),
+            false => SUBSTITUTIONS
+                .iter()
+                .find(|(u, _)| *u == c)
+                .map_or("?".to_string(), |(_, a)| a.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn energy_breakpoints() {
+        assert_eq!(format_energy(Some(10.0)), "10.00");
+        assert_eq!(format_energy(Some(9.999)), "9.999");
+        assert_eq!(format_energy(Some(0.001)), "0.001");
+        assert_eq!(format_energy(Some(0.0009)), "9.00e-4");
+    }
+
+    #[test]
+    fn intensity_breakpoints() {
+        assert_eq!(format_intensity(Some(100.0)), "100.0");
+        assert_eq!(format_intensity(Some(99.5)), "99.50");
+        assert_eq!(format_intensity(Some(10.0)), "10.00");
+        assert_eq!(format_intensity(Some(9.5)), "9.500");
+        assert_eq!(format_intensity(Some(0.001)), "0.001");
+        assert_eq!(format_intensity(Some(0.0005)), "5.00e-4");
+    }
+
+    #[test]
+    fn branching_breakpoints() {
+        assert_eq!(format_branching(Some(100.0), 1), "");
+        assert_eq!(format_branching(Some(45.3), 1), "(45.3%)");
+        assert_eq!(format_branching(Some(1.0), 1), "(1.0%)");
+    }
+
+    #[test]
+    fn branching_boundaries() {
+        assert_eq!(format_branching(Some(100.0), 1), "");
+        assert_eq!(format_branching(Some(99.99), 1), "");
+        assert_eq!(format_branching(Some(1.0), 1), "(1.0%)");
+        assert_eq!(format_branching(Some(0.999), 1), "(1.0%)");
+        assert_eq!(format_branching(Some(0.99), 1), "(9.9e-1%)");
+        assert_eq!(format_branching(Some(0.001), 1), "(1.0e-3%)");
+        assert_eq!(format_branching(Some(0.999), 2), "(9.99e-1%)");
+    }
+
+    #[test]
+    fn halflife_units() {
+        assert_eq!(human_readable_halflife(Some(30.0)), "30.00 s");
+        assert_eq!(human_readable_halflife(Some(90.0)), "1.50 minutes");
+        assert_eq!(human_readable_halflife(Some(1.8e-3)), "1.80 ms");
+    }
+
+    #[test]
+    fn missing_values() {
+        assert_eq!(format_energy(None), "  -");
+        assert_eq!(format_intensity(None), "  -");
+        assert_eq!(format_branching(None, 1), "None");
+        assert_eq!(human_readable_halflife(None), "-");
+    }
+}
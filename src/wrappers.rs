@@ -22,6 +22,14 @@ impl CliRadType {
             CliRadType::Electron => "electron",
         }
     }
+
+    /// Continuous spectra given by endpoint energies, i.e. beta and electron
+    pub fn is_beta(&self) -> bool {
+        matches!(
+            self,
+            CliRadType::BetaMinus | CliRadType::BetaPlus | CliRadType::Electron
+        )
+    }
 }
 
 impl From<CliRadType> for ntools::iaea::RadType {